@@ -0,0 +1,84 @@
+pub mod atomic {
+    use std::fmt;
+    use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+    use std::sync::Arc;
+    /*
+    Counter to give every atomic cell a unique id
+     */
+    static NEXT_ATOMIC_ID: AtomicU64 = AtomicU64::new(1);
+    struct AtomicState {
+        id: u64,
+        value: AtomicI64,
+    }
+    /*
+    Integer shared by every task holding a copy of the cell, updated without locking
+     */
+    #[derive(Clone)]
+    pub struct AtomicCell {
+        state: Arc<AtomicState>,
+    }
+
+    impl AtomicCell {
+        pub fn new(value: i64) -> AtomicCell {
+            AtomicCell {
+                state: Arc::new(AtomicState {
+                    id: NEXT_ATOMIC_ID.fetch_add(1, Ordering::Relaxed),
+                    value: AtomicI64::new(value),
+                }),
+            }
+        }
+        pub fn load(&self) -> i64 {
+            self.state.value.load(Ordering::SeqCst)
+        }
+        pub fn store(&self, value: i64) {
+            self.state.value.store(value, Ordering::SeqCst)
+        }
+        /*
+        Add delta (wrapping on overflow) and return the previous value
+         */
+        pub fn fetch_add(&self, delta: i64) -> i64 {
+            self.state.value.fetch_add(delta, Ordering::SeqCst)
+        }
+        /*
+        Store new value only if the cell still holds expected, true when the value was stored
+         */
+        pub fn compare_and_swap(&self, expected: i64, new: i64) -> bool {
+            self.state.value.compare_exchange(expected, new, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+        }
+    }
+    impl PartialEq for AtomicCell {
+        fn eq(&self, other: &AtomicCell) -> bool {
+            Arc::ptr_eq(&self.state, &other.state)
+        }
+    }
+    impl Eq for AtomicCell {}
+    impl fmt::Debug for AtomicCell {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Atomic({})", self.state.id)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::atomic::atomic::AtomicCell;
+    use std::thread;
+    #[test]
+    fn copies_of_cell_update_same_value() {
+        let cell = AtomicCell::new(0);
+        let workers: Vec<_> = (0..4).map(|_| {
+            let cell = cell.clone();
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    cell.fetch_add(1);
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(cell.load(), 4000);
+        assert!(!cell.compare_and_swap(1, 2));
+        assert!(cell.compare_and_swap(4000, 7));
+        assert_eq!(cell.fetch_add(1), 7);
+    }
+}
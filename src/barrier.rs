@@ -0,0 +1,110 @@
+pub mod barrier {
+    use crate::waker::waker::{WaitQueue, Waker};
+    use std::fmt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    /*
+    Counter to give every barrier a unique id
+     */
+    static NEXT_BARRIER_ID: AtomicU64 = AtomicU64::new(1);
+    /*
+    Tasks that arrived in the current round, the round number changes when the last task arrives
+     */
+    struct BarrierState {
+        parties: usize,
+        arrived: usize,
+        generation: u64,
+        waiters: WaitQueue,
+    }
+    struct BarrierCell {
+        id: u64,
+        state: Mutex<BarrierState>,
+    }
+    /*
+    Barrier shared by every task holding a copy of it, created by BARRIER n.
+    The barrier can be used again once all tasks of a round passed it
+     */
+    #[derive(Clone)]
+    pub struct Barrier {
+        cell: Arc<BarrierCell>,
+    }
+
+    impl Barrier {
+        pub fn new(parties: usize) -> Barrier {
+            Barrier {
+                cell: Arc::new(BarrierCell {
+                    id: NEXT_BARRIER_ID.fetch_add(1, Ordering::Relaxed),
+                    state: Mutex::new(BarrierState { parties: parties.max(1), arrived: 0, generation: 0, waiters: WaitQueue::default() }),
+                }),
+            }
+        }
+        /*
+        Count the task as arrived. None when it was the last one and the round is over,
+        otherwise the round it has to wait for with poll_passed
+         */
+        pub fn arrive(&self) -> Option<u64> {
+            let mut state = self.cell.state.lock().unwrap();
+            state.arrived += 1;
+            if state.arrived == state.parties {
+                state.arrived = 0;
+                state.generation += 1;
+                state.waiters.wake_all();
+                return None;
+            }
+            Some(state.generation)
+        }
+        /*
+        Some once round generation is over, otherwise register waker (when given) and return None
+         */
+        pub fn poll_passed(&self, generation: u64, waker: Option<&Waker>) -> Option<()> {
+            let mut state = self.cell.state.lock().unwrap();
+            if state.generation != generation {
+                return Some(());
+            }
+            if let Some(waker) = waker {
+                state.waiters.register(waker);
+            }
+            None
+        }
+    }
+    impl PartialEq for Barrier {
+        fn eq(&self, other: &Barrier) -> bool {
+            Arc::ptr_eq(&self.cell, &other.cell)
+        }
+    }
+    impl Eq for Barrier {}
+    impl fmt::Debug for Barrier {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Barrier({})", self.cell.id)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::barrier::barrier::Barrier;
+    use crate::waker::waker::block_on;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    #[test]
+    fn nobody_passes_before_all_arrived() {
+        let barrier = Barrier::new(3);
+        let arrived = Arc::new(AtomicUsize::new(0));
+        let workers: Vec<_> = (0..3).map(|_| {
+            let (barrier, arrived) = (barrier.clone(), arrived.clone());
+            thread::spawn(move || {
+                for round in 1..=2 {
+                    arrived.fetch_add(1, Ordering::SeqCst);
+                    if let Some(generation) = barrier.arrive() {
+                        block_on(|waker| barrier.poll_passed(generation, Some(waker)));
+                    }
+                    assert!(arrived.load(Ordering::SeqCst) >= 3 * round);
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(arrived.load(Ordering::SeqCst), 6);
+    }
+}
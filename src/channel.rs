@@ -0,0 +1,549 @@
+pub mod channel {
+    use crate::clock::clock::Clock;
+    use crate::interpreter::interpreter::{ByteCodeDataTypes, ByteCodeError};
+    use crate::waker::waker::{block_on, WaitQueue, Waker};
+    use std::collections::VecDeque;
+    use std::fmt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    /*
+    Counter to give every channel a unique id, shared by its sender and receiver end points
+     */
+    static NEXT_CHANNEL_ID: AtomicU64 = AtomicU64::new(1);
+    /*
+    State of the random sequence used to pick the first arm polled by select
+     */
+    static SELECT_SEED: AtomicU64 = AtomicU64::new(0x9e37_79b9_7f4a_7c15);
+    /*
+    Buffered values, the tasks waiting to send or receive and the number of live end points.
+    The channel is closed by CLOSE_CHANNEL or when every end point on one side was dropped.
+    A timer channel has no senders, the timer acts as its sender until it fired for the last time
+     */
+    struct ChannelState {
+        queue: VecDeque<ByteCodeDataTypes>,
+        capacity: Option<usize>,
+        senders: WaitQueue,
+        receivers: WaitQueue,
+        sender_count: usize,
+        receiver_count: usize,
+        closed: bool,
+        timer: Option<Timer>,
+    }
+    /*
+    Time the next tick is due on the clock of the VM (None once a one-shot timer fired), the interval of a ticker
+    and the number of ticks so far. Ticks are produced when a receiver polls the channel, ticks missed by a slow
+    receiver are dropped
+     */
+    struct Timer {
+        clock: Arc<Clock>,
+        next: Option<Instant>,
+        period: Option<Duration>,
+        ticks: i64,
+    }
+    struct Channel {
+        id: u64,
+        state: Mutex<ChannelState>,
+    }
+    /*
+    Subscribers of a broadcast channel, each subscriber has its own unbounded channel
+     */
+    struct BroadcastState {
+        subscribers: Vec<ChannelSender>,
+        closed: bool,
+    }
+    struct Broadcast {
+        id: u64,
+        state: Mutex<BroadcastState>,
+    }
+    /*
+    Channel a sender writes to, a broadcast sender writes a copy of each value to every subscriber
+     */
+    enum SenderTarget {
+        Channel(Arc<Channel>),
+        Broadcast(Arc<Broadcast>),
+    }
+    /*
+    Sending end point of a channel, can be stored in variables, lists and other channels
+     */
+    pub struct ChannelSender {
+        target: SenderTarget,
+    }
+    /*
+    Receiving end point of a channel, copies of the value share the same queue
+     */
+    pub struct ChannelReceiver {
+        channel: Arc<Channel>,
+    }
+    /*
+    Received value of a completed receive operation, None for a send operation
+     */
+    pub type SelectResult = Result<Option<ByteCodeDataTypes>, ByteCodeError>;
+    /*
+    Operation of one SELECT arm
+     */
+    pub enum SelectOp {
+        Receive(ChannelReceiver),
+        Send(ChannelSender, Option<ByteCodeDataTypes>),
+    }
+    /*
+    Create new channel, unbounded when no capacity is given. Capacity 0 makes every send wait for a receiver
+     */
+    pub fn channel(capacity: Option<usize>) -> (ChannelSender, ChannelReceiver) {
+        let channel = Arc::new(Channel {
+            id: NEXT_CHANNEL_ID.fetch_add(1, Ordering::Relaxed),
+            state: Mutex::new(ChannelState {
+                queue: VecDeque::new(),
+                capacity,
+                senders: WaitQueue::default(),
+                receivers: WaitQueue::default(),
+                sender_count: 1,
+                receiver_count: 1,
+                closed: false,
+                timer: None,
+            }),
+        });
+        (ChannelSender { target: SenderTarget::Channel(channel.clone()) }, ChannelReceiver { channel })
+    }
+    /*
+    Create channel receiving the tick count after delay, once or with a ticker every period after that.
+    Closing the receiver stops the timer
+     */
+    pub fn timer(clock: &Arc<Clock>, delay: Duration, period: Option<Duration>) -> ChannelReceiver {
+        let (tx, rx) = channel(None);
+        {
+            let mut state = rx.channel.state.lock().unwrap();
+            // the timer counts as sender, it is set before the only sender is dropped
+            state.timer = Some(Timer {
+                clock: clock.clone(),
+                next: Some(clock.now() + delay),
+                period: period.map(|p| p.max(Duration::from_millis(1))),
+                ticks: 0,
+            });
+        }
+        drop(tx);
+        rx
+    }
+    /*
+    Create new broadcast channel, receivers are created with subscribe
+     */
+    pub fn broadcast() -> ChannelSender {
+        let broadcast = Broadcast {
+            id: NEXT_CHANNEL_ID.fetch_add(1, Ordering::Relaxed),
+            state: Mutex::new(BroadcastState { subscribers: Vec::new(), closed: false }),
+        };
+        ChannelSender { target: SenderTarget::Broadcast(Arc::new(broadcast)) }
+    }
+
+    impl ChannelState {
+        fn close(&mut self) {
+            self.closed = true;
+            self.timer = None;
+            self.senders.wake_all();
+            self.receivers.wake_all();
+        }
+        /*
+        No value can be sent anymore
+         */
+        fn disconnected(&self) -> bool {
+            self.closed || (self.sender_count == 0 && self.timer.is_none())
+        }
+        /*
+        Queue the next tick of the timer if it is due
+         */
+        fn fire_timer(&mut self) {
+            let Some(timer) = self.timer.as_mut() else { return };
+            let Some(mut next) = timer.next else { return };
+            let now = timer.clock.now();
+            if now < next {
+                return;
+            }
+            timer.ticks += 1;
+            self.queue.push_back(ByteCodeDataTypes::Integer64(timer.ticks));
+            // a fired one-shot timer stays open but is never ready again
+            timer.next = timer.period.map(|period| {
+                while next <= now {
+                    next += period;
+                }
+                next
+            });
+        }
+    }
+
+    impl ChannelSender {
+        /*
+        Send value taken from the option if there is room for it, otherwise register waker (when given)
+        and return None leaving the value in place
+         */
+        pub fn poll_send(&self, value: &mut Option<ByteCodeDataTypes>, waker: Option<&Waker>) -> Option<Result<(), ByteCodeError>> {
+            let channel = match &self.target {
+                SenderTarget::Channel(channel) => channel,
+                SenderTarget::Broadcast(broadcast) => return Some(broadcast.send(value.take().unwrap())),
+            };
+            let mut state = channel.state.lock().unwrap();
+            if state.closed || state.receiver_count == 0 {
+                return Some(Err(ByteCodeError::ChannelClosed));
+            }
+            let has_room = match state.capacity {
+                None => true,
+                Some(0) => state.queue.len() < state.receivers.len(),
+                Some(capacity) => state.queue.len() < capacity,
+            };
+            if has_room {
+                state.queue.push_back(value.take().unwrap());
+                state.receivers.wake_all();
+                Some(Ok(()))
+            } else {
+                if let Some(waker) = waker {
+                    state.senders.register(waker);
+                }
+                None
+            }
+        }
+        /*
+        Send value, waiting while a bounded channel is full
+         */
+        pub fn send(&self, value: ByteCodeDataTypes) -> Result<(), ByteCodeError> {
+            let mut value = Some(value);
+            block_on(|waker| self.poll_send(&mut value, Some(waker)))
+        }
+        /*
+        Send value without waiting, false when the channel is full
+         */
+        pub fn try_send(&self, value: ByteCodeDataTypes) -> Result<bool, ByteCodeError> {
+            self.poll_send(&mut Some(value), None).map_or(Ok(false), |sent| sent.map(|_| true))
+        }
+        pub fn close(&self) {
+            match &self.target {
+                SenderTarget::Channel(channel) => channel.state.lock().unwrap().close(),
+                SenderTarget::Broadcast(broadcast) => {
+                    let mut state = broadcast.state.lock().unwrap();
+                    state.closed = true;
+                    for subscriber in state.subscribers.drain(..) {
+                        subscriber.close();
+                    }
+                },
+            }
+        }
+        /*
+        True when the channel was closed or every receiver was dropped
+         */
+        pub fn is_closed(&self) -> bool {
+            match &self.target {
+                SenderTarget::Channel(channel) => {
+                    let state = channel.state.lock().unwrap();
+                    state.closed || state.receiver_count == 0
+                },
+                SenderTarget::Broadcast(broadcast) => broadcast.state.lock().unwrap().closed,
+            }
+        }
+        /*
+        New receiver of a broadcast channel that gets every value sent from now on, None for other channels
+         */
+        pub fn subscribe(&self) -> Option<ChannelReceiver> {
+            match &self.target {
+                SenderTarget::Channel(_) => None,
+                SenderTarget::Broadcast(broadcast) => {
+                    let mut state = broadcast.state.lock().unwrap();
+                    let (tx, rx) = channel(None);
+                    if state.closed {
+                        tx.close();
+                    } else {
+                        state.subscribers.push(tx);
+                    }
+                    Some(rx)
+                },
+            }
+        }
+        fn id(&self) -> u64 {
+            match &self.target {
+                SenderTarget::Channel(channel) => channel.id,
+                SenderTarget::Broadcast(broadcast) => broadcast.id,
+            }
+        }
+    }
+
+    impl Broadcast {
+        /*
+        Copy value to every subscriber, subscribers whose receivers were all dropped are removed
+         */
+        fn send(&self, value: ByteCodeDataTypes) -> Result<(), ByteCodeError> {
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return Err(ByteCodeError::ChannelClosed);
+            }
+            state.subscribers.retain(|subscriber| subscriber.send(value.clone()).is_ok());
+            Ok(())
+        }
+    }
+
+    impl ChannelReceiver {
+        /*
+        Take next value if there is one, otherwise register waker (when given) and return None.
+        Values sent before the channel was closed are still received
+         */
+        pub fn poll_recv(&self, waker: Option<&Waker>) -> Option<Result<ByteCodeDataTypes, ByteCodeError>> {
+            let mut state = self.channel.state.lock().unwrap();
+            state.fire_timer();
+            if let Some(v) = state.queue.pop_front() {
+                state.senders.wake_all();
+                return Some(Ok(v));
+            }
+            if state.disconnected() {
+                return Some(Err(ByteCodeError::ChannelClosed));
+            }
+            if let Some(waker) = waker {
+                state.receivers.register(waker);
+                // a sender of a channel without buffer can hand over its value now
+                state.senders.wake_all();
+            }
+            None
+        }
+        /*
+        Receive without waiting, None when the channel is empty
+         */
+        pub fn try_recv(&self) -> Result<Option<ByteCodeDataTypes>, ByteCodeError> {
+            self.poll_recv(None).transpose()
+        }
+        pub fn close(&self) {
+            self.channel.state.lock().unwrap().close();
+        }
+        /*
+        True when the channel was closed or every sender was dropped, buffered values may still be received
+         */
+        pub fn is_closed(&self) -> bool {
+            self.channel.state.lock().unwrap().disconnected()
+        }
+        /*
+        When the next tick of a timer channel is due, the receiving task has to be woken then
+         */
+        pub fn deadline(&self) -> Option<Instant> {
+            self.channel.state.lock().unwrap().timer.as_ref().and_then(|timer| timer.next)
+        }
+    }
+    impl Clone for ChannelSender {
+        fn clone(&self) -> ChannelSender {
+            match &self.target {
+                SenderTarget::Channel(channel) => {
+                    channel.state.lock().unwrap().sender_count += 1;
+                    ChannelSender { target: SenderTarget::Channel(channel.clone()) }
+                },
+                SenderTarget::Broadcast(broadcast) => ChannelSender { target: SenderTarget::Broadcast(broadcast.clone()) },
+            }
+        }
+    }
+    impl Clone for ChannelReceiver {
+        fn clone(&self) -> ChannelReceiver {
+            self.channel.state.lock().unwrap().receiver_count += 1;
+            ChannelReceiver { channel: self.channel.clone() }
+        }
+    }
+    /*
+    Dropping the last end point of one side disconnects the channel and wakes the other side.
+    Subscribers of a broadcast channel are disconnected when its last sender is dropped
+     */
+    impl Drop for ChannelSender {
+        fn drop(&mut self) {
+            if let SenderTarget::Channel(channel) = &self.target {
+                let mut state = channel.state.lock().unwrap();
+                state.sender_count -= 1;
+                if state.sender_count == 0 {
+                    state.receivers.wake_all();
+                }
+            }
+        }
+    }
+    impl Drop for ChannelReceiver {
+        fn drop(&mut self) {
+            let mut state = self.channel.state.lock().unwrap();
+            state.receiver_count -= 1;
+            if state.receiver_count == 0 {
+                state.senders.wake_all();
+            }
+        }
+    }
+    /*
+    Random index below len from a splitmix64 sequence
+     */
+    fn random_index(len: usize) -> usize {
+        let mut z = SELECT_SEED.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        ((z ^ (z >> 31)) % len as u64) as usize
+    }
+    /*
+    Try every operation once starting at index start, registering the waker on those that are not ready
+     */
+    fn poll_select(ops: &mut [SelectOp], start: usize, waker: Option<&Waker>) -> Option<(usize, SelectResult)> {
+        let len = ops.len();
+        for i in (0..len).map(|k| (start + k) % len) {
+            let result = match &mut ops[i] {
+                SelectOp::Receive(rx) => rx.poll_recv(waker).map(|received| received.map(Some)),
+                SelectOp::Send(tx, value) => tx.poll_send(value, waker).map(|sent| sent.map(|_| None)),
+            };
+            if let Some(result) = result {
+                return Some((i, result));
+            }
+        }
+        None
+    }
+    /*
+    Complete one ready operation without waiting, otherwise register waker (when given) on every
+    operation and return None. Used by tasks that wait in the runtime instead of blocking the thread.
+    Polling starts at index start when given (e.g. chosen by a seeded scheduler), otherwise at a random index
+     */
+    pub fn try_select(ops: &mut [SelectOp], start: Option<usize>, waker: Option<&Waker>) -> Option<(usize, SelectResult)> {
+        if ops.is_empty() {
+            return None;
+        }
+        poll_select(ops, start.unwrap_or_else(|| random_index(ops.len())), waker)
+    }
+    impl PartialEq for ChannelSender {
+        fn eq(&self, other: &ChannelSender) -> bool {
+            self.id() == other.id()
+        }
+    }
+    impl Eq for ChannelSender {}
+    impl PartialEq for ChannelReceiver {
+        fn eq(&self, other: &ChannelReceiver) -> bool {
+            self.channel.id == other.channel.id
+        }
+    }
+    impl Eq for ChannelReceiver {}
+    impl fmt::Debug for ChannelSender {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match &self.target {
+                SenderTarget::Channel(channel) => write!(f, "Channel({})", channel.id),
+                SenderTarget::Broadcast(broadcast) => write!(f, "Broadcast({})", broadcast.id),
+            }
+        }
+    }
+    impl fmt::Debug for ChannelReceiver {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Channel({})", self.channel.id)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::channel::channel::{broadcast, channel, timer, try_select, ChannelReceiver, SelectOp};
+    use crate::clock::clock::Clock;
+    use std::sync::Arc;
+    use crate::interpreter::interpreter::{ByteCodeDataTypes, ByteCodeError};
+    use crate::waker::waker::{block_on, block_until, Waker};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    /*
+    Block until poll returns a value, waking up at the next tick of timer since nothing else wakes the thread then
+     */
+    fn wait_ticking<T>(timer: &ChannelReceiver, mut poll: impl FnMut(&Waker) -> Option<T>) -> T {
+        loop {
+            if let Some(v) = block_until(timer.deadline(), &mut poll) {
+                return v;
+            }
+        }
+    }
+    fn recv(rx: &ChannelReceiver) -> Result<ByteCodeDataTypes, ByteCodeError> {
+        wait_ticking(rx, |waker| rx.poll_recv(Some(waker)))
+    }
+    #[test]
+    fn copies_of_end_points_share_the_channel() {
+        let (tx, rx) = channel(None);
+        let (tx2, rx2) = (tx.clone(), rx.clone());
+        assert_eq!(tx, tx2);
+        assert_ne!(tx, channel(None).0);
+        tx.send(ByteCodeDataTypes::Integer64(1)).unwrap();
+        tx2.send(ByteCodeDataTypes::Integer64(2)).unwrap();
+        assert_eq!(recv(&rx2).unwrap(), ByteCodeDataTypes::Integer64(1));
+        assert_eq!(recv(&rx).unwrap(), ByteCodeDataTypes::Integer64(2));
+    }
+    #[test]
+    fn bounded_channel_rejects_try_send_when_full() {
+        let (tx, rx) = channel(Some(1));
+        assert!(tx.try_send(ByteCodeDataTypes::Integer64(1)).unwrap());
+        assert!(!tx.try_send(ByteCodeDataTypes::Integer64(2)).unwrap());
+        assert_eq!(rx.try_recv().unwrap(), Some(ByteCodeDataTypes::Integer64(1)));
+        assert!(rx.try_recv().unwrap().is_none());
+        assert!(block_until(Some(Instant::now() + Duration::from_millis(10)), |waker| rx.poll_recv(Some(waker))).is_none());
+    }
+    #[test]
+    fn unbuffered_send_waits_for_receiver() {
+        let (tx, rx) = channel(Some(0));
+        assert!(!tx.try_send(ByteCodeDataTypes::Integer64(1)).unwrap());
+        let sender = thread::spawn(move || tx.send(ByteCodeDataTypes::Integer64(2)));
+        assert_eq!(recv(&rx).unwrap(), ByteCodeDataTypes::Integer64(2));
+        assert!(sender.join().unwrap().is_ok());
+    }
+    #[test]
+    fn closed_channel_is_drained_then_reports_closed() {
+        let (tx, rx) = channel(None);
+        tx.send(ByteCodeDataTypes::Integer64(1)).unwrap();
+        tx.close();
+        assert!(tx.is_closed() && rx.is_closed());
+        assert!(matches!(tx.send(ByteCodeDataTypes::Integer64(2)), Err(ByteCodeError::ChannelClosed)));
+        assert_eq!(recv(&rx).unwrap(), ByteCodeDataTypes::Integer64(1));
+        assert!(matches!(recv(&rx), Err(ByteCodeError::ChannelClosed)));
+        let (tx, rx) = channel(None);
+        let waiting = thread::spawn(move || recv(&rx));
+        drop(tx.clone());
+        thread::sleep(Duration::from_millis(10));
+        drop(tx);
+        assert!(matches!(waiting.join().unwrap(), Err(ByteCodeError::ChannelClosed)));
+    }
+    #[test]
+    fn every_subscriber_receives_every_broadcast_value() {
+        let tx = broadcast();
+        let (rx1, rx2) = (tx.subscribe().unwrap(), tx.subscribe().unwrap());
+        tx.send(ByteCodeDataTypes::Integer64(1)).unwrap();
+        let rx3 = tx.clone().subscribe().unwrap();
+        tx.send(ByteCodeDataTypes::Integer64(2)).unwrap();
+        assert_eq!(recv(&rx1).unwrap(), ByteCodeDataTypes::Integer64(1));
+        assert_eq!(recv(&rx2).unwrap(), ByteCodeDataTypes::Integer64(1));
+        assert_eq!(recv(&rx3).unwrap(), ByteCodeDataTypes::Integer64(2));
+        drop(tx);
+        assert_eq!(recv(&rx1).unwrap(), ByteCodeDataTypes::Integer64(2));
+        assert!(matches!(recv(&rx1), Err(ByteCodeError::ChannelClosed)));
+        assert!(channel(None).0.subscribe().is_none());
+    }
+    #[test]
+    fn select_picks_ready_operation_and_is_fair() {
+        let (tx1, rx1) = channel(None);
+        let (tx2, rx2) = channel(None);
+        let (full, _keep) = channel(Some(0));
+        let mut picked = [0; 2];
+        for _ in 0..200 {
+            tx1.send(ByteCodeDataTypes::Integer64(1)).unwrap();
+            tx2.send(ByteCodeDataTypes::Integer64(2)).unwrap();
+            let mut ops = vec![SelectOp::Receive(rx1.clone()), SelectOp::Receive(rx2.clone()),
+                               SelectOp::Send(full.clone(), Some(ByteCodeDataTypes::None))];
+            let (index, value) = block_on(|waker| try_select(&mut ops, None, Some(waker)));
+            assert_eq!(value.unwrap(), Some(ByteCodeDataTypes::Integer64(index as i64 + 1)));
+            picked[index] += 1;
+            while rx1.try_recv().unwrap().is_some() || rx2.try_recv().unwrap().is_some() {}
+        }
+        assert!(picked[0] > 50 && picked[1] > 50);
+        let mut ops = vec![SelectOp::Receive(rx1)];
+        assert!(try_select(&mut ops, None, None).is_none());
+        let deadline = Instant::now() + Duration::from_millis(10);
+        assert!(block_until(Some(deadline), |waker| try_select(&mut ops, None, Some(waker))).is_none());
+        assert!(Instant::now() >= deadline);
+    }
+    #[test]
+    fn timers_tick_until_closed() {
+        let clock = Arc::new(Clock::new());
+        let after = timer(&clock, Duration::from_millis(10), None);
+        assert!(after.try_recv().unwrap().is_none() && !after.is_closed());
+        assert_eq!(recv(&after).unwrap(), ByteCodeDataTypes::Integer64(1));
+        assert!(after.deadline().is_none() && !after.is_closed() && after.try_recv().unwrap().is_none());
+        after.close();
+        assert!(matches!(recv(&after), Err(ByteCodeError::ChannelClosed)));
+        let ticker = timer(&clock, Duration::from_millis(5), Some(Duration::from_millis(5)));
+        let (_tx, never) = channel(None);
+        let mut ops = vec![SelectOp::Receive(never), SelectOp::Receive(ticker.clone())];
+        let (index, value) = wait_ticking(&ticker, |waker| try_select(&mut ops, None, Some(waker)));
+        assert_eq!((index, value.unwrap()), (1, Some(ByteCodeDataTypes::Integer64(1))));
+        assert!(matches!(recv(&ticker).unwrap(), ByteCodeDataTypes::Integer64(n) if n >= 2));
+        ticker.close();
+        assert!(ticker.deadline().is_none() && matches!(recv(&ticker), Err(ByteCodeError::ChannelClosed)));
+    }
+}
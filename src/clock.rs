@@ -0,0 +1,74 @@
+pub mod clock {
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+    /*
+    Time seen by the tasks of a VM. A virtual clock stands still while any task can run and is advanced
+    by the runtime to the next deadline when every task waits, so sleeps and timers take no real time
+     */
+    pub struct Clock {
+        start: Instant,
+        elapsed: Mutex<Option<Duration>>,
+    }
+
+    impl Clock {
+        /*
+        Clock following the wall clock until it is made virtual
+         */
+        pub fn new() -> Clock {
+            Clock { start: Instant::now(), elapsed: Mutex::new(None) }
+        }
+        /*
+        Switch to virtual time starting at zero, must be done before tasks read the clock
+         */
+        pub fn set_virtual(&self) {
+            *self.elapsed.lock().unwrap() = Some(Duration::ZERO);
+        }
+        pub fn is_virtual(&self) -> bool {
+            self.elapsed.lock().unwrap().is_some()
+        }
+        pub fn now(&self) -> Instant {
+            match *self.elapsed.lock().unwrap() {
+                Some(elapsed) => self.start + elapsed,
+                None => Instant::now(),
+            }
+        }
+        /*
+        Move virtual time forward to deadline, it never goes back. Does nothing for the wall clock
+         */
+        pub fn advance_to(&self, deadline: Instant) {
+            if let Some(elapsed) = self.elapsed.lock().unwrap().as_mut() {
+                *elapsed = (*elapsed).max(deadline.saturating_duration_since(self.start));
+            }
+        }
+        /*
+        Milliseconds read by NOW - since the Unix epoch for the wall clock, since the start of the VM for virtual time
+         */
+        pub fn millis(&self) -> i64 {
+            match *self.elapsed.lock().unwrap() {
+                Some(elapsed) => elapsed.as_millis() as i64,
+                None => SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64,
+            }
+        }
+    }
+    impl Default for Clock {
+        fn default() -> Clock {
+            Clock::new()
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::clock::clock::Clock;
+    use std::time::Duration;
+    #[test]
+    fn virtual_time_only_moves_forward_when_advanced() {
+        let clock = Clock::new();
+        clock.set_virtual();
+        let start = clock.now();
+        assert_eq!((clock.now(), clock.millis()), (start, 0));
+        clock.advance_to(start + Duration::from_millis(1500));
+        clock.advance_to(start + Duration::from_millis(10));
+        assert_eq!((clock.now() - start, clock.millis()), (Duration::from_millis(1500), 1500));
+        assert!(Clock::new().millis() > 1_600_000_000_000);
+    }
+}
@@ -0,0 +1,271 @@
+pub mod decimal {
+    use std::cmp::Ordering;
+    use std::fmt;
+    use std::str::FromStr;
+    /*
+    Largest number of digits allowed after the decimal point
+     */
+    pub const MAX_SCALE: u32 = 28;
+    /*
+    Fixed-point decimal stored as mantissa * 10^-scale, e.g. 12.50 is mantissa 1250 with scale 2
+     */
+    #[derive(Clone, Copy)]
+    pub struct Decimal {
+        mantissa: i128,
+        scale: u32,
+    }
+    /*
+    Rounding modes used when a result has more digits than the requested scale
+     */
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum RoundingMode {
+        Down,
+        Up,
+        Floor,
+        Ceiling,
+        HalfUp,
+        HalfDown,
+        HalfEven,
+    }
+    /*
+    Error returned when a decimal literal or rounding mode can not be parsed
+     */
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct ParseDecimalError;
+    /*
+    10 to the power of exp, None if it does not fit in i128
+     */
+    fn pow10(exp: u32) -> Option<i128> {
+        10i128.checked_pow(exp)
+    }
+    /*
+    Divide n by d and round the quotient according to rounding mode
+     */
+    fn div_round(n: i128, d: i128, mode: RoundingMode) -> Option<i128> {
+        let q = n.checked_div(d)?;
+        let r = n % d;
+        if r == 0 {
+            return Some(q);
+        }
+        let sign = if (n < 0) != (d < 0) { -1 } else { 1 };
+        let half = r.unsigned_abs().cmp(&(d.unsigned_abs() - r.unsigned_abs()));
+        let away = match mode {
+            RoundingMode::Down => false,
+            RoundingMode::Up => true,
+            RoundingMode::Floor => sign < 0,
+            RoundingMode::Ceiling => sign > 0,
+            RoundingMode::HalfUp => half != Ordering::Less,
+            RoundingMode::HalfDown => half == Ordering::Greater,
+            RoundingMode::HalfEven => half == Ordering::Greater || (half == Ordering::Equal && q % 2 != 0),
+        };
+        if away { q.checked_add(sign) } else { Some(q) }
+    }
+
+    impl Decimal {
+        pub fn new(mantissa: i128, scale: u32) -> Option<Decimal> {
+            if scale > MAX_SCALE { None } else { Some(Decimal { mantissa, scale }) }
+        }
+        pub fn from_i64(v: i64) -> Decimal {
+            Decimal { mantissa: v as i128, scale: 0 }
+        }
+        pub fn is_zero(&self) -> bool {
+            self.mantissa == 0
+        }
+        /*
+        Mantissa of this value expressed at a larger scale, None on overflow
+         */
+        fn mantissa_at(&self, scale: u32) -> Option<i128> {
+            self.mantissa.checked_mul(pow10(scale - self.scale)?)
+        }
+        /*
+        Change the number of digits after the decimal point, rounding when digits are dropped
+         */
+        pub fn rescale(self, scale: u32, mode: RoundingMode) -> Option<Decimal> {
+            if scale > MAX_SCALE {
+                return None;
+            }
+            if scale >= self.scale {
+                Decimal::new(self.mantissa_at(scale)?, scale)
+            } else {
+                Decimal::new(div_round(self.mantissa, pow10(self.scale - scale)?, mode)?, scale)
+            }
+        }
+        pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+            let scale = self.scale.max(other.scale);
+            Decimal::new(self.mantissa_at(scale)?.checked_add(other.mantissa_at(scale)?)?, scale)
+        }
+        pub fn checked_sub(self, other: Decimal) -> Option<Decimal> {
+            let scale = self.scale.max(other.scale);
+            Decimal::new(self.mantissa_at(scale)?.checked_sub(other.mantissa_at(scale)?)?, scale)
+        }
+        /*
+        Exact product, the scale of the result is the sum of both scales
+         */
+        pub fn checked_mul(self, other: Decimal) -> Option<Decimal> {
+            Decimal::new(self.mantissa.checked_mul(other.mantissa)?, self.scale + other.scale)
+        }
+        /*
+        Division at the larger scale of both operands using banker's rounding
+         */
+        pub fn checked_div(self, other: Decimal) -> Option<Decimal> {
+            self.div_round(other, self.scale.max(other.scale), RoundingMode::HalfEven)
+        }
+        pub fn checked_rem(self, other: Decimal) -> Option<Decimal> {
+            let scale = self.scale.max(other.scale);
+            Decimal::new(self.mantissa_at(scale)?.checked_rem(other.mantissa_at(scale)?)?, scale)
+        }
+        /*
+        Product rounded to the given scale
+         */
+        pub fn mul_round(self, other: Decimal, scale: u32, mode: RoundingMode) -> Option<Decimal> {
+            let product = self.mantissa.checked_mul(other.mantissa)?;
+            let product_scale = self.scale + other.scale;
+            if product_scale <= scale {
+                Decimal::new(product.checked_mul(pow10(scale - product_scale)?)?, scale)
+            } else {
+                Decimal::new(div_round(product, pow10(product_scale - scale)?, mode)?, scale)
+            }
+        }
+        /*
+        Quotient rounded to the given scale, None on division by zero or overflow
+         */
+        pub fn div_round(self, other: Decimal, scale: u32, mode: RoundingMode) -> Option<Decimal> {
+            if other.is_zero() {
+                return None;
+            }
+            // self / other = (m1 / m2) * 10^(s2 - s1), so the mantissa at scale s is m1 * 10^(s + s2 - s1) / m2
+            let exp = scale as i64 + other.scale as i64 - self.scale as i64;
+            let mantissa = if exp >= 0 {
+                div_round(self.mantissa.checked_mul(pow10(exp as u32)?)?, other.mantissa, mode)?
+            } else {
+                div_round(self.mantissa, other.mantissa.checked_mul(pow10((-exp) as u32)?)?, mode)?
+            };
+            Decimal::new(mantissa, scale)
+        }
+    }
+    impl PartialEq for Decimal {
+        fn eq(&self, other: &Decimal) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+    impl Eq for Decimal {}
+    /*
+    Compare by value regardless of scale, so 12.5 equals 12.50
+     */
+    impl Ord for Decimal {
+        fn cmp(&self, other: &Decimal) -> Ordering {
+            let scale = self.scale.max(other.scale);
+            match (self.mantissa_at(scale), other.mantissa_at(scale)) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => {
+                    let (pa, pb) = (pow10(self.scale).unwrap(), pow10(other.scale).unwrap());
+                    (self.mantissa / pa).cmp(&(other.mantissa / pb)).then_with(|| {
+                        let fa = (self.mantissa % pa) * pow10(scale - self.scale).unwrap();
+                        let fb = (other.mantissa % pb) * pow10(scale - other.scale).unwrap();
+                        fa.cmp(&fb)
+                    })
+                }
+            }
+        }
+    }
+    impl PartialOrd for Decimal {
+        fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    /*
+    Parses literals like 12.50, -0.001 or 7, the scale is the number of digits after the point
+     */
+    impl FromStr for Decimal {
+        type Err = ParseDecimalError;
+        fn from_str(s: &str) -> Result<Decimal, ParseDecimalError> {
+            let (negative, digits) = match s.strip_prefix('-') {
+                Some(rest) => (true, rest),
+                None => (false, s.strip_prefix('+').unwrap_or(s)),
+            };
+            let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+            if int_part.is_empty() && frac_part.is_empty() {
+                return Err(ParseDecimalError);
+            }
+            let mut mantissa: i128 = 0;
+            for c in int_part.chars().chain(frac_part.chars()) {
+                let digit = c.to_digit(10).ok_or(ParseDecimalError)?;
+                mantissa = mantissa.checked_mul(10)
+                    .and_then(|m| m.checked_add(digit as i128))
+                    .ok_or(ParseDecimalError)?;
+            }
+            if negative {
+                mantissa = -mantissa;
+            }
+            Decimal::new(mantissa, frac_part.len() as u32).ok_or(ParseDecimalError)
+        }
+    }
+    impl FromStr for RoundingMode {
+        type Err = ParseDecimalError;
+        fn from_str(s: &str) -> Result<RoundingMode, ParseDecimalError> {
+            match s {
+                "DOWN" => Ok(RoundingMode::Down),
+                "UP" => Ok(RoundingMode::Up),
+                "FLOOR" => Ok(RoundingMode::Floor),
+                "CEILING" => Ok(RoundingMode::Ceiling),
+                "HALF_UP" => Ok(RoundingMode::HalfUp),
+                "HALF_DOWN" => Ok(RoundingMode::HalfDown),
+                "HALF_EVEN" => Ok(RoundingMode::HalfEven),
+                _ => Err(ParseDecimalError),
+            }
+        }
+    }
+    impl fmt::Display for Decimal {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let sign = if self.mantissa < 0 { "-" } else { "" };
+            let digits = self.mantissa.unsigned_abs().to_string();
+            if self.scale == 0 {
+                return write!(f, "{}{}", sign, digits);
+            }
+            let digits = format!("{:0>width$}", digits, width = self.scale as usize + 1);
+            let (int_part, frac_part) = digits.split_at(digits.len() - self.scale as usize);
+            write!(f, "{}{}.{}", sign, int_part, frac_part)
+        }
+    }
+    impl fmt::Debug for Decimal {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::decimal::decimal::{Decimal, RoundingMode};
+    fn dec(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+    #[test]
+    fn parse_format_and_compare() {
+        assert_eq!(format!("{}", dec("12.50")), "12.50");
+        assert_eq!(format!("{}", dec("-0.05")), "-0.05");
+        assert_eq!(dec("12.5"), dec("12.500"));
+        assert!(dec("-1.01") < dec("-1.001"));
+        assert!("1.2.3".parse::<Decimal>().is_err());
+    }
+    #[test]
+    fn rounding_modes() {
+        let cases = [
+            ("2.345", RoundingMode::HalfEven, "2.34"), ("2.355", RoundingMode::HalfEven, "2.36"),
+            ("2.345", RoundingMode::HalfUp, "2.35"), ("2.345", RoundingMode::HalfDown, "2.34"),
+            ("-2.341", RoundingMode::Floor, "-2.35"), ("-2.349", RoundingMode::Ceiling, "-2.34"),
+            ("2.341", RoundingMode::Up, "2.35"), ("-2.349", RoundingMode::Down, "-2.34"),
+        ];
+        for (value, mode, expected) in cases {
+            assert_eq!(format!("{}", dec(value).rescale(2, mode).unwrap()), expected);
+        }
+    }
+    #[test]
+    fn arithmetic() {
+        assert_eq!(format!("{}", dec("12.50").checked_add(dec("0.125")).unwrap()), "12.625");
+        assert_eq!(format!("{}", dec("1.10").checked_mul(dec("3.3")).unwrap()), "3.630");
+        assert_eq!(format!("{}", dec("10.00").checked_div(dec("3")).unwrap()), "3.33");
+        assert_eq!(format!("{}", dec("2").div_round(dec("3"), 4, RoundingMode::Up).unwrap()), "0.6667");
+        assert_eq!(format!("{}", dec("19.99").mul_round(dec("0.075"), 2, RoundingMode::HalfUp).unwrap()), "1.50");
+        assert_eq!(dec("1").checked_div(dec("0.00")), None);
+    }
+}
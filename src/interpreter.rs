@@ -1,9 +1,19 @@
 pub mod interpreter {
     use std::collections::HashMap;
-    use std::thread;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::Arc;
     use std::time::Duration;
-    use std::sync::{mpsc};
-    use std::sync::mpsc::{channel, Sender, Receiver};
+    use crate::u256::u256::U256;
+    use crate::decimal::decimal::{Decimal, RoundingMode};
+    use crate::task::task::TaskHandle;
+    use crate::mutex::mutex::SharedMutex;
+    use crate::atomic::atomic::AtomicCell;
+    use crate::barrier::barrier::Barrier;
+    use crate::wait_group::wait_group::WaitGroup;
+    use crate::channel::channel::{broadcast, channel, timer, try_select, ChannelSender, ChannelReceiver, SelectOp};
+    use crate::mutex::mutex::LockGuard;
+    use crate::runtime::runtime::{BlockedTask, Context, Vm};
+    use std::time::Instant;
     /*
     Enum for all bytecode instruction set
      */
@@ -11,28 +21,108 @@ pub mod interpreter {
     #[allow(dead_code)]
     pub enum ByteCode {
         LoadVar(i64),
+        LoadU256(U256),
+        LoadDecimal(Decimal),
         WriteVar(&'static str),
         ReadVar(&'static str),
         Add,
         Multiply,
         Subtract,
         Divide,
+        Modulo,
+        BitAnd,
+        BitOr,
+        BitXor,
+        BitNot,
+        ShiftLeft,
+        ShiftRight,
+        ToU256,
+        ToI64,
+        MulDecimal(u32, RoundingMode),
+        DivDecimal(u32, RoundingMode),
+        RoundDecimal(u32, RoundingMode),
         LessThan,
         LessThanEqual,
         GreaterThan,
         GreaterThanEqual,
         Loop(Vec<ByteCode>, Vec<ByteCode>),
         FunctionCall(&'static str, Vec<&'static str>),
+        LoadFunction(&'static str),
+        Closure(&'static str, Vec<&'static str>),
+        CallIndirect(Vec<&'static str>),
+        List(usize),
+        ListGet,
+        ListPush,
+        ListLen,
         Print(&'static str),
         PrintLn(&'static str),
         Sleep(u64),
+        SleepMs(u64),
+        After(u64, Option<&'static str>),
+        Ticker(u64, Option<&'static str>),
+        Now,
+        Yield,
         Spawn(Vec<ByteCode>, Vec<&'static str>),
+        SpawnFunctions(usize, usize),
+        ParMap(Option<&'static str>),
+        ParReduce(Option<&'static str>),
+        Join(Option<&'static str>),
+        JoinAll,
+        Cancel(Option<&'static str>),
+        SelfTask,
+        SendTo(Option<&'static str>),
+        Receive,
         Mutex(&'static str),
-        Channel(&'static str, &'static str),
-        SendChannel(&'static str),
-        ReceiveChannel(&'static str),
+        WithLock(&'static str, Vec<ByteCode>),
+        Atomic(&'static str),
+        AtomicAdd(&'static str),
+        AtomicLoad(&'static str),
+        AtomicStore(&'static str),
+        CompareAndSwap(&'static str),
+        WaitGroup(&'static str),
+        WaitGroupAdd(i64, &'static str),
+        WaitGroupDone(&'static str),
+        WaitGroupWait(&'static str),
+        Barrier(usize, &'static str),
+        BarrierWait(&'static str),
+        Channel(Option<usize>, Option<(&'static str, &'static str)>),
+        SendChannel(Option<&'static str>),
+        ReceiveChannel(Option<&'static str>),
+        TrySend(Option<&'static str>),
+        TryReceive(Option<&'static str>),
+        ReceiveTimeout(u64, Option<&'static str>),
+        CloseChannel(Option<&'static str>),
+        CloneSender(Option<&'static str>),
+        Broadcast(Option<&'static str>),
+        Subscribe(Option<&'static str>),
+        IsClosed(Option<&'static str>),
+        ReceiveLoop(&'static str, Vec<ByteCode>),
+        Select(Vec<(SelectCase, Vec<ByteCode>)>),
+        Supervise(Supervision, Option<&'static str>, Vec<ByteCode>),
         ReturnValue,
         Return,
+        Unknown,
+    }
+    /*
+    What happens when a supervised task fails - run it again from the start up to n times,
+    finish it with None or fail it with the error like an unsupervised task
+     */
+    #[derive(Debug, PartialEq, Clone)]
+    pub enum Supervision {
+        Restart(usize),
+        Ignore,
+        Escalate,
+    }
+    /*
+    Arm of a SELECT block - receive from channel variable, send value of variable on channel variable,
+    timeout in milliseconds or default when no other arm is ready
+     */
+    #[derive(Debug, PartialEq, Clone)]
+    pub enum SelectCase {
+        Receive(&'static str),
+        Send(&'static str, &'static str),
+        Timeout(u64),
+        Default,
     }
     /*
     Byte code supported data types
@@ -41,22 +131,42 @@ pub mod interpreter {
     #[allow(dead_code)]
     pub enum ByteCodeDataTypes {
         Integer64(i64),
+        U256(U256),
+        Decimal(Decimal),
         Boolean(bool),
+        Function(FunctionValue),
+        List(Vec<ByteCodeDataTypes>),
+        Task(TaskHandle),
+        Sender(ChannelSender),
+        Receiver(ChannelReceiver),
+        Mutex(SharedMutex),
+        Atomic(AtomicCell),
+        WaitGroup(WaitGroup),
+        Barrier(Barrier),
+        Error(ByteCodeError),
         None,
     }
     /*
-    Byte code supported Channel specific data types
+    Values are moved to other threads through channels and spawned tasks, so every data type must be Send.
+    Plain values are owned and copied with the value, channel end points and task handles are shared handles
      */
-    #[derive(Debug)]
-    #[allow(dead_code)]
-    pub enum ByteCodeMpscSyncTypes {
-        SendChannel(Sender<i64>),
-        ReceiveChannel(Receiver<i64>),
+    const _: fn() = || {
+        fn assert_send<T: Send>() {}
+        assert_send::<ByteCodeDataTypes>();
+    };
+    /*
+    Function reference with variables captured from the defining scope (empty for LOAD_FUNC)
+    Captured values are a snapshot taken when the closure is created
+     */
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    pub struct FunctionValue {
+        pub name: &'static str,
+        pub captured: Vec<(&'static str, ByteCodeDataTypes)>,
     }
     /*
     Enum for bytecode execution errors
      */
-    #[derive(Debug)]
+    #[derive(Debug, PartialEq, Eq, Clone)]
     #[allow(dead_code)]
     pub enum ByteCodeError {
         DivisionByZero,
@@ -64,41 +174,152 @@ pub mod interpreter {
         UnknownByteCode,
         NoReturnOpcode,
         ChannelNotFound,
+        Overflow,
+        TypeMismatch,
+        FunctionNotFound,
+        NotAFunction,
+        IndexOutOfBounds,
+        ChannelClosed,
+        LockPoisoned,
+        NegativeWaitGroup,
+        Cancelled,
+        Panicked(String),
+        Deadlock(Vec<BlockedTask>),
     }
     /*
-    Program structure to hold bytecode, stack, global variables, parameters and function bytecodes
+    Function bytecodes shared by every block and task of a program
+     */
+    type Functions = Arc<HashMap<&'static str, Arc<Vec<ByteCode>>>>;
+    /*
+    Program structure to hold bytecode, stack, global variables and function bytecodes.
+    One program is one frame of a task - the main code, a function call or a nested block,
+    pc is the instruction executed next and resume what the instruction did before it had to wait.
+    A nested block may run past its last instruction without leaving a value
      */
     #[allow(dead_code)]
     pub struct Program {
-        code: Vec<ByteCode>,
+        code: Arc<Vec<ByteCode>>,
+        pc: usize,
         stack: Vec<ByteCodeDataTypes>,
         global_vars: HashMap<&'static str, ByteCodeDataTypes>,
-        parameters: HashMap<&'static str, ByteCodeMpscSyncTypes>,
-        #[allow(dead_code)]
-        functions: HashMap<&'static str, Vec<ByteCode>>,
+        functions: Functions,
+        resume: Option<Resume>,
+        nested: bool,
     }
     /*
-    Macro to get value from enum type ByteCodeDataTypes
+    State of an instruction that is waiting for a nested block or for another task
      */
-    macro_rules! value {
-        ($var:expr) => {
-            match $var {
-                ByteCodeDataTypes::Integer64(v) => v,
-                ByteCodeDataTypes::Boolean(_) => todo!(),
-                ByteCodeDataTypes::None => todo!(),
+    enum Resume {
+        LoopCondition,
+        LoopCode,
+        Block,
+        Receive(ChannelReceiver),
+        Lock(LockGuard, ByteCodeDataTypes, SharedMutex),
+        Until(Instant),
+        Barrier(u64),
+        Parallel(FunctionValue, Vec<TaskHandle>, Vec<ByteCodeDataTypes>),
+        Switched,
+    }
+    /*
+    Outcome of executing one instruction - continue, run a nested block first, wait until woken,
+    let other tasks run or finish the block with its result and variables,
+    or end a nested block that ran past its last instruction without result
+     */
+    enum Step {
+        Next,
+        Call(Program),
+        Pending,
+        Yield,
+        Done(Result<ByteCodeDataTypes, ByteCodeError>, HashMap<&'static str, ByteCodeDataTypes>),
+        Ended(HashMap<&'static str, ByteCodeDataTypes>),
+    }
+    /*
+    Outcome of running a task until it finishes or can not continue
+     */
+    pub enum Run {
+        Done(Result<ByteCodeDataTypes, ByteCodeError>, HashMap<&'static str, ByteCodeDataTypes>),
+        Pending,
+        Yield,
+    }
+    /*
+    Task state - the stack of programs (frames) being executed, the innermost last
+     */
+    pub struct Machine {
+        frames: Vec<Program>,
+        supervisor: Option<Supervisor>,
+    }
+    /*
+    Supervision of a task - its policy, the restarts used so far, the channel failures are reported to
+    and the code, stack and variables it is started with again. A failure whose report waits for room
+    in the channel is kept until it was sent
+     */
+    struct Supervisor {
+        policy: Supervision,
+        restarts: usize,
+        reports: Option<ChannelSender>,
+        start: Program,
+        failure: Option<(ByteCodeError, Option<ByteCodeDataTypes>)>,
+    }
+    /*
+    Number of instructions a task runs before it lets other tasks run
+     */
+    const STEPS_PER_SLICE: usize = 10_000;
+    impl ByteCodeDataTypes {
+        /*
+        Check for zero divisor of any numeric type
+         */
+        fn is_zero(&self) -> bool {
+            match self {
+                ByteCodeDataTypes::Integer64(v) => *v == 0,
+                ByteCodeDataTypes::U256(v) => v.is_zero(),
+                ByteCodeDataTypes::Decimal(v) => v.is_zero(),
+                _ => false,
             }
-        };
+        }
+        /*
+        Integer64 values are promoted to decimals with scale 0 when mixed with decimals
+         */
+        fn to_decimal(&self) -> Option<Decimal> {
+            match self {
+                ByteCodeDataTypes::Integer64(v) => Some(Decimal::from_i64(*v)),
+                ByteCodeDataTypes::Decimal(v) => Some(*v),
+                _ => None,
+            }
+        }
     }
     /*
-    Macro to perform mathematics operation on values from stack - add, subtract, multiply and divide
+    Macro to perform mathematics operation on values from stack - add, subtract, multiply, divide and modulo
+    using checked operations, a result that does not fit the type ends the task with Overflow
      */
     macro_rules! operation {
-    ($code:expr,$op:tt) => {
+    ($code:expr,$checked:ident) => {
             if let (Some(a1), Some(b1)) = ($code.stack.pop(), $code.stack.pop()) {
-                let a = value!(a1);
-                let b = value!(b1);
-                $code.stack.push(ByteCodeDataTypes::Integer64(b $op a));
-                None
+                match (b1, a1) {
+                    (ByteCodeDataTypes::Integer64(b), ByteCodeDataTypes::Integer64(a)) => match b.$checked(a) {
+                        Some(v) => {
+                            $code.stack.push(ByteCodeDataTypes::Integer64(v));
+                            None
+                        },
+                        None => return Step::Done(Err(ByteCodeError::Overflow), HashMap::new()),
+                    },
+                    (ByteCodeDataTypes::U256(b), ByteCodeDataTypes::U256(a)) => match b.$checked(a) {
+                        Some(v) => {
+                            $code.stack.push(ByteCodeDataTypes::U256(v));
+                            None
+                        },
+                        None => return Step::Done(Err(ByteCodeError::Overflow), HashMap::new()),
+                    },
+                    (b, a) => match (b.to_decimal(), a.to_decimal()) {
+                        (Some(b), Some(a)) => match b.$checked(a) {
+                            Some(v) => {
+                                $code.stack.push(ByteCodeDataTypes::Decimal(v));
+                                None
+                            },
+                            None => return Step::Done(Err(ByteCodeError::Overflow), HashMap::new()),
+                        },
+                        _ => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                    },
+                }
             } else {
                 Some(ByteCodeError::StackUnderflow)
             }
@@ -110,10 +331,95 @@ pub mod interpreter {
     macro_rules! compare {
     ($code:expr,$op:tt) => {
             if let (Some(a1), Some(b1)) = ($code.stack.pop(), $code.stack.pop()) {
-                let a = value!(a1);
-                let b = value!(b1);
-                $code.stack.push(ByteCodeDataTypes::Boolean(b $op a));
-                None
+                match (b1, a1) {
+                    (ByteCodeDataTypes::Integer64(b), ByteCodeDataTypes::Integer64(a)) => {
+                        $code.stack.push(ByteCodeDataTypes::Boolean(b $op a));
+                        None
+                    },
+                    (ByteCodeDataTypes::U256(b), ByteCodeDataTypes::U256(a)) => {
+                        $code.stack.push(ByteCodeDataTypes::Boolean(b $op a));
+                        None
+                    },
+                    (b, a) => match (b.to_decimal(), a.to_decimal()) {
+                        (Some(b), Some(a)) => {
+                            $code.stack.push(ByteCodeDataTypes::Boolean(b $op a));
+                            None
+                        },
+                        _ => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                    },
+                }
+            } else {
+                Some(ByteCodeError::StackUnderflow)
+            }
+        }
+    }
+    /*
+    Macro to perform decimal operation with explicit result scale and rounding mode on values from stack
+     */
+    macro_rules! decimal_operation {
+    ($code:expr,$method:ident,$scale:expr,$mode:expr) => {
+            if let (Some(a1), Some(b1)) = ($code.stack.pop(), $code.stack.pop()) {
+                match (b1.to_decimal(), a1.to_decimal()) {
+                    (Some(b), Some(a)) => match b.$method(a, *$scale, *$mode) {
+                        Some(v) => {
+                            $code.stack.push(ByteCodeDataTypes::Decimal(v));
+                            None
+                        },
+                        None => Some(ByteCodeError::Overflow),
+                    },
+                    _ => Some(ByteCodeError::TypeMismatch),
+                }
+            } else {
+                Some(ByteCodeError::StackUnderflow)
+            }
+        }
+    }
+    /*
+    Macro to perform bitwise operation on values from stack - and, or, xor
+     */
+    macro_rules! bitwise {
+    ($code:expr,$op:tt,$method:ident) => {
+            if let (Some(a1), Some(b1)) = ($code.stack.pop(), $code.stack.pop()) {
+                match (b1, a1) {
+                    (ByteCodeDataTypes::Integer64(b), ByteCodeDataTypes::Integer64(a)) => {
+                        $code.stack.push(ByteCodeDataTypes::Integer64(b $op a));
+                        None
+                    },
+                    (ByteCodeDataTypes::U256(b), ByteCodeDataTypes::U256(a)) => {
+                        $code.stack.push(ByteCodeDataTypes::U256(b.$method(a)));
+                        None
+                    },
+                    (ByteCodeDataTypes::Boolean(b), ByteCodeDataTypes::Boolean(a)) => {
+                        $code.stack.push(ByteCodeDataTypes::Boolean(b $op a));
+                        None
+                    },
+                    _ => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                }
+            } else {
+                Some(ByteCodeError::StackUnderflow)
+            }
+        }
+    }
+    /*
+    Macro to shift value from stack by number of bits on top of stack - shift left, shift right
+     */
+    macro_rules! shift {
+    ($code:expr,$op:tt,$method:ident) => {
+            if let (Some(a1), Some(b1)) = ($code.stack.pop(), $code.stack.pop()) {
+                match (b1, a1) {
+                    (ByteCodeDataTypes::Integer64(b), ByteCodeDataTypes::Integer64(a)) if (0..64).contains(&a) => {
+                        $code.stack.push(ByteCodeDataTypes::Integer64(b $op a));
+                        None
+                    },
+                    (ByteCodeDataTypes::U256(b), ByteCodeDataTypes::Integer64(a)) if a >= 0 => {
+                        $code.stack.push(ByteCodeDataTypes::U256(b.$method(a.min(256) as u32)));
+                        None
+                    },
+                    (ByteCodeDataTypes::Integer64(_) | ByteCodeDataTypes::U256(_), ByteCodeDataTypes::Integer64(_)) => {
+                        return Step::Done(Err(ByteCodeError::Overflow), HashMap::new());
+                    },
+                    _ => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                }
             } else {
                 Some(ByteCodeError::StackUnderflow)
             }
@@ -138,214 +444,1252 @@ pub mod interpreter {
         };
     }
     /*
-    Move values from one hash map to another hash map
+    Move variables (usually channel end points) out of the current block into a new hash map,
+    the current block can not use them afterwards. A missing variable fails like READ_VAR
      */
     #[macro_export]
     macro_rules! move_parameters {
-        ($mpsc_data:expr,$parameter_vars:expr) => {{
+        ($global_vars:expr,$parameter_vars:expr) => {{
             let mut parameters = HashMap::new();
-            for i in 0..$parameter_vars.len() {
-                parameters.insert($parameter_vars[i], $mpsc_data.remove($parameter_vars[i]).unwrap());
+            for var in $parameter_vars.iter() {
+                match $global_vars.remove(var) {
+                    Some(value) => {
+                        parameters.insert(*var, value);
+                    },
+                    None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                }
             }
             parameters
         }};
     }
     /*
-    Interpret and execute the byte code
+    Get value (channel end point or task handle) from named variable, or pop it from stack when no name is given
+     */
+    macro_rules! named_or_popped {
+        ($program:expr,$var:expr) => {
+            match $var {
+                Some(var) => $program.global_vars.get(var).cloned(),
+                None => $program.stack.pop(),
+            }
+        };
+    }
+    /*
+    Push received value (None when nothing was received) followed by success flag to stack,
+    receiving from a closed channel ends the block with the error
+     */
+    macro_rules! push_received {
+        ($program:expr,$received:expr) => {
+            match $received {
+                Ok(Some(v)) => {
+                    $program.stack.push(v);
+                    $program.stack.push(ByteCodeDataTypes::Boolean(true));
+                },
+                Ok(None) => {
+                    $program.stack.push(ByteCodeDataTypes::None);
+                    $program.stack.push(ByteCodeDataTypes::Boolean(false));
+                },
+                Err(e) => return Step::Done(Err(e), HashMap::new()),
+            }
+        };
+    }
+    /*
+    Get atomic cell from named variable
+     */
+    macro_rules! atomic_var {
+        ($program:expr,$var:expr) => {
+            match $program.global_vars.get($var) {
+                Some(ByteCodeDataTypes::Atomic(cell)) => cell.clone(),
+                _ => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+            }
+        };
+    }
+    /*
+    Get wait group from named variable
+     */
+    macro_rules! wait_group_var {
+        ($program:expr,$var:expr) => {
+            match $program.global_vars.get($var) {
+                Some(ByteCodeDataTypes::WaitGroup(wg)) => wg.clone(),
+                _ => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+            }
+        };
+    }
+    /*
+    Get barrier from named variable
+     */
+    macro_rules! barrier_var {
+        ($program:expr,$var:expr) => {
+            match $program.global_vars.get($var) {
+                Some(ByteCodeDataTypes::Barrier(barrier)) => barrier.clone(),
+                _ => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+            }
+        };
+    }
+    /*
+    Call function by name with moved channel parameters, its return value is pushed to stack when it finishes
+    Captured variables of a closure take precedence over variables of the calling block
+     */
+    macro_rules! call_function {
+        ($program:expr,$func_code:expr,$captured:expr,$parameter_vars:expr) => {{
+            let parameters = move_parameters!($program.global_vars, $parameter_vars);
+            let mut vars = $program.global_vars.clone();
+            vars.extend(parameters);
+            vars.extend($captured);
+            let func_code = $program.functions.get($func_code).unwrap().clone();
+            return Step::Call(Program::new(func_code, Vec::new(), vars, $program.functions.clone()));
+        }};
+    }
+    /*
+    Interpret and execute the byte code, spawned tasks run on their own threads
      */
     #[allow(dead_code)]
     pub fn execute(code: Vec<ByteCode>, stack: Vec<ByteCodeDataTypes>,
                    global_vars: HashMap<&'static str, ByteCodeDataTypes>,
-                   parameters: HashMap<&'static str, ByteCodeMpscSyncTypes>,
                    functions: HashMap<&'static str, Vec<ByteCode>>) -> (Result<ByteCodeDataTypes, ByteCodeError>, HashMap<&'static str, ByteCodeDataTypes>) {
-        let mut program = Program {
-            code,
-            stack,
-            global_vars,
-            parameters,
-            functions,
+        Vm::new().execute(code, stack, global_vars, functions)
+    }
+
+    impl Program {
+        fn new(code: Arc<Vec<ByteCode>>, stack: Vec<ByteCodeDataTypes>,
+               global_vars: HashMap<&'static str, ByteCodeDataTypes>, functions: Functions) -> Program {
+            Program { code, pc: 0, stack, global_vars, functions, resume: None, nested: false }
+        }
+        /*
+        Nested block (loop, lock or select arm) working on the variables of this block,
+        they are moved to the block and moved back when it finishes
+         */
+        fn block(&mut self, code: &[ByteCode], stack: Vec<ByteCodeDataTypes>) -> Program {
+            let vars = std::mem::take(&mut self.global_vars);
+            let mut block = Program::new(Arc::new(code.to_vec()), stack, vars, self.functions.clone());
+            block.nested = true;
+            block
+        }
+        /*
+        Deadline of the waiting instruction, set the first time it is executed
+         */
+        fn deadline(&mut self, now: Instant, timeout: Duration) -> Instant {
+            match self.resume {
+                Some(Resume::Until(deadline)) => deadline,
+                _ => {
+                    let deadline = now + timeout;
+                    self.resume = Some(Resume::Until(deadline));
+                    deadline
+                },
+            }
+        }
+        /*
+        New task running code with copies of the variables
+         */
+        fn spawn(&self, code: Arc<Vec<ByteCode>>, stack: Vec<ByteCodeDataTypes>,
+                 global_vars: HashMap<&'static str, ByteCodeDataTypes>) -> Machine {
+            Machine { frames: vec![Program::new(code, stack, global_vars, self.functions.clone())], supervisor: None }
+        }
+        /*
+        New program starting the same code with copies of the stack and variables
+         */
+        fn restart(&self) -> Program {
+            Program::new(self.code.clone(), self.stack.clone(), self.global_vars.clone(), self.functions.clone())
+        }
+    }
+
+    impl Machine {
+        pub fn new(code: Vec<ByteCode>, stack: Vec<ByteCodeDataTypes>,
+                   global_vars: HashMap<&'static str, ByteCodeDataTypes>,
+                   functions: HashMap<&'static str, Vec<ByteCode>>) -> Machine {
+            let functions = functions.into_iter().map(|(name, code)| (name, Arc::new(code))).collect();
+            Machine { frames: vec![Program::new(Arc::new(code), stack, global_vars, Arc::new(functions))], supervisor: None }
+        }
+        /*
+        Supervise the task with policy, failures are sent to reports (dropped when it is closed)
+         */
+        fn supervise(&mut self, policy: Supervision, reports: Option<ChannelSender>) {
+            let start = self.frames[0].restart();
+            self.supervisor = Some(Supervisor { policy, restarts: 0, reports, start, failure: None });
+        }
+        /*
+        Execute instructions until the task finishes, has to wait (the waker of the context is registered
+        on what it waits for) or used up its time slice. A panic fails the task with Panicked
+         */
+        pub fn resume(&mut self, cx: &mut Context) -> Run {
+            match panic::catch_unwind(AssertUnwindSafe(|| self.run(cx))) {
+                Ok(run) => run,
+                Err(panic) => {
+                    // dropping the frames poisons the mutexes locked by the task
+                    self.frames.clear();
+                    let message = match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+                        (Some(message), _) => message.to_string(),
+                        (_, Some(message)) => message.clone(),
+                        _ => String::new(),
+                    };
+                    self.finished(Err(ByteCodeError::Panicked(message)), HashMap::new(), cx).unwrap_or(Run::Yield)
+                },
+            }
+        }
+        /*
+        Outermost frame finished, a supervised task that failed is reported and handled by its policy.
+        None when the task was restarted
+         */
+        fn finished(&mut self, result: Result<ByteCodeDataTypes, ByteCodeError>,
+                    vars: HashMap<&'static str, ByteCodeDataTypes>, cx: &mut Context) -> Option<Run> {
+            let supervisor = match (&mut self.supervisor, &result) {
+                (Some(supervisor), Err(e)) if *e != ByteCodeError::Cancelled => supervisor,
+                _ => return Some(Run::Done(result, vars)),
+            };
+            let error = result.unwrap_err();
+            let report = supervisor.reports.as_ref().map(|_| {
+                ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Task(cx.task().clone()), ByteCodeDataTypes::Error(error.clone())])
+            });
+            supervisor.failure = Some((error, report));
+            self.handle_failure(cx)
+        }
+        /*
+        Send the report of the failure, waiting while the channel is full, then apply the policy.
+        None when the task was restarted
+         */
+        fn handle_failure(&mut self, cx: &mut Context) -> Option<Run> {
+            let supervisor = self.supervisor.as_mut().unwrap();
+            let (error, mut report) = supervisor.failure.take().unwrap();
+            if let (Some(reports), Some(_)) = (&supervisor.reports, &report) {
+                if reports.poll_send(&mut report, Some(cx.waker())).is_none() {
+                    cx.blocked_on("SUPERVISE", format!("{:?}", reports));
+                    supervisor.failure = Some((error, report));
+                    return Some(Run::Pending);
+                }
+            }
+            match supervisor.policy {
+                Supervision::Restart(restarts) if supervisor.restarts < restarts => {
+                    supervisor.restarts += 1;
+                    self.frames = vec![supervisor.start.restart()];
+                    None
+                },
+                Supervision::Ignore => Some(Run::Done(Ok(ByteCodeDataTypes::None), HashMap::new())),
+                _ => Some(Run::Done(Err(error), HashMap::new())),
+            }
+        }
+        fn run(&mut self, cx: &mut Context) -> Run {
+            // a failed supervised task has no frames while it waits to send its report
+            if self.frames.is_empty() {
+                if cx.is_cancelled() {
+                    return Run::Done(Err(ByteCodeError::Cancelled), HashMap::new());
+                }
+                if let Some(run) = self.handle_failure(cx) {
+                    return run;
+                }
+            }
+            for _ in 0..STEPS_PER_SLICE {
+                let mut next = step(self.frames.last_mut().unwrap(), cx);
+                loop {
+                    match next {
+                        Step::Next => break,
+                        Step::Call(program) => {
+                            self.frames.push(program);
+                            break;
+                        },
+                        // a cancelled task stops at the first wait, the waker of the context was woken by cancel
+                        Step::Pending if cx.is_cancelled() => {
+                            self.frames.clear();
+                            return Run::Done(Err(ByteCodeError::Cancelled), HashMap::new());
+                        },
+                        Step::Pending => return Run::Pending,
+                        Step::Yield => return Run::Yield,
+                        Step::Ended(vars) => {
+                            self.frames.pop();
+                            next = block_finished(self.frames.last_mut().unwrap(), None, vars, cx);
+                        },
+                        Step::Done(result, vars) => {
+                            self.frames.pop();
+                            match self.frames.last_mut() {
+                                Some(parent) => next = block_finished(parent, Some(result), vars, cx),
+                                None => match self.finished(result, vars, cx) {
+                                    Some(run) => return run,
+                                    None => break,
+                                },
+                            }
+                        },
+                    }
+                }
+            }
+            Run::Yield
+        }
+    }
+    /*
+    Continue the instruction that started a nested block or function call with its result,
+    None when the block ran past its end with an empty stack
+     */
+    fn block_finished(program: &mut Program, result: Option<Result<ByteCodeDataTypes, ByteCodeError>>,
+                      vars: HashMap<&'static str, ByteCodeDataTypes>, cx: &Context) -> Step {
+        let code = program.code.clone();
+        match (&code[program.pc], program.resume.take()) {
+            (ByteCode::Loop(loop_condition, loop_code), Some(Resume::LoopCondition)) => {
+                program.global_vars = vars;
+                match result {
+                    Some(Ok(ByteCodeDataTypes::Boolean(false))) => {},
+                    Some(Ok(ByteCodeDataTypes::Boolean(true))) => {
+                        program.resume = Some(Resume::LoopCode);
+                        return Step::Call(program.block(loop_code, Vec::new()));
+                    },
+                    Some(Ok(_)) => {
+                        program.resume = Some(Resume::LoopCondition);
+                        return Step::Call(program.block(loop_condition, Vec::new()));
+                    },
+                    Some(Err(e)) => return Step::Done(Err(e), HashMap::new()),
+                    None => return Step::Done(Err(ByteCodeError::UnknownByteCode), HashMap::new()),
+                }
+            },
+            (ByteCode::Loop(loop_condition, _), Some(Resume::LoopCode)) => {
+                program.global_vars = vars;
+                if cx.is_cancelled() {
+                    return Step::Done(Err(ByteCodeError::Cancelled), HashMap::new());
+                }
+                program.resume = Some(Resume::LoopCondition);
+                return Step::Call(program.block(loop_condition, Vec::new()));
+            },
+            (ByteCode::FunctionCall(..) | ByteCode::CallIndirect(_), _) => match result {
+                Some(Ok(r)) => program.stack.push(r),
+                Some(Err(e)) => return Step::Done(Err(e), HashMap::new()),
+                None => return Step::Done(Err(ByteCodeError::UnknownByteCode), HashMap::new()),
+            },
+            (ByteCode::WithLock(var, _), Some(Resume::Lock(guard, value, mutex))) => {
+                // Inside the block the variable holds the protected value, its final value is stored back
+                let mut vars = vars;
+                match result {
+                    None | Some(Ok(_)) => {
+                        guard.unlock(vars.remove(var).unwrap_or(value));
+                    },
+                    Some(Err(e)) => {
+                        guard.poison();
+                        return Step::Done(Err(e), HashMap::new());
+                    },
+                }
+                vars.insert(var, ByteCodeDataTypes::Mutex(mutex));
+                program.global_vars = vars;
+            },
+            (ByteCode::Select(_), Some(Resume::Block)) => match result {
+                Some(Err(e)) => return Step::Done(Err(e), HashMap::new()),
+                _ => program.global_vars = vars,
+            },
+            (ByteCode::ReceiveLoop(..), resume) => {
+                if let Some(Err(e)) = result {
+                    return Step::Done(Err(e), HashMap::new());
+                }
+                // receive the next value
+                program.global_vars = vars;
+                if cx.is_cancelled() {
+                    return Step::Done(Err(ByteCodeError::Cancelled), HashMap::new());
+                }
+                program.resume = resume;
+                return Step::Next;
+            },
+            _ => program.global_vars = vars,
+        }
+        program.pc += 1;
+        Step::Next
+    }
+    /*
+    Start function as a task for every initial stack in args, with copies of the variables and the captured values
+     */
+    fn spawn_all(program: &Program, cx: &Context, func: &FunctionValue, args: Vec<Vec<ByteCodeDataTypes>>) -> Vec<TaskHandle> {
+        let func_code = program.functions.get(func.name).unwrap().clone();
+        args.into_iter().map(|stack| {
+            let mut vars_copy = program.global_vars.clone();
+            vars_copy.extend(func.captured.clone());
+            cx.spawn(program.spawn(func_code.clone(), stack, vars_copy))
+        }).collect()
+    }
+    /*
+    Results of the tasks in order, None while the task waits for them. The error of the first failed task
+    is returned once the tasks before it finished, no matter which task failed first, the tasks after it are cancelled
+     */
+    fn join_in_order(handles: &[TaskHandle], cx: &mut Context, opcode: &'static str) -> Option<Result<Vec<ByteCodeDataTypes>, ByteCodeError>> {
+        let polled: Vec<_> = handles.iter().map(|handle| handle.poll_join(Some(cx.waker()))).collect();
+        let failed = polled.iter().position(|result| matches!(result, Some(Err(_))));
+        if let Some(failed) = failed {
+            for handle in &handles[failed + 1..] {
+                handle.cancel();
+            }
+        }
+        let decided = failed.map_or(polled.len(), |failed| failed + 1);
+        if let Some(waiting) = polled[..decided].iter().position(Option::is_none) {
+            cx.blocked_on(opcode, format!("{:?}", handles[waiting]));
+            return None;
+        }
+        Some(polled.into_iter().take(decided).map(Option::unwrap).collect())
+    }
+    /*
+    Record that the task waits for rx, a timer channel has to wake the task when its next tick is due
+     */
+    fn wait_receiving(cx: &mut Context, opcode: &'static str, rx: &ChannelReceiver) {
+        if let Some(deadline) = rx.deadline() {
+            cx.wait_until(deadline);
+        }
+        cx.blocked_on(opcode, format!("{:?}", rx));
+    }
+    /*
+    Instructions where tasks interact through channels, locks and atomic cells
+     */
+    fn switch_point(bc: &ByteCode) -> bool {
+        matches!(bc, ByteCode::SendChannel(_) | ByteCode::ReceiveChannel(_) | ByteCode::TrySend(_)
+            | ByteCode::TryReceive(_) | ByteCode::ReceiveTimeout(..) | ByteCode::Select(_) | ByteCode::WithLock(..)
+            | ByteCode::AtomicAdd(_) | ByteCode::AtomicLoad(_) | ByteCode::AtomicStore(_) | ByteCode::CompareAndSwap(_)
+            | ByteCode::WaitGroupDone(_) | ByteCode::WaitGroupWait(_) | ByteCode::BarrierWait(_)
+            | ByteCode::SendTo(_) | ByteCode::Receive)
+    }
+    /*
+    Execute the instruction at pc of the program
+     */
+    fn step(program: &mut Program, cx: &mut Context) -> Step {
+        let code = program.code.clone();
+        let Some(bc) = code.get(program.pc) else {
+            let vars = std::mem::take(&mut program.global_vars);
+            return match program.stack.pop() {
+                Some(res) => Step::Done(Ok(res), vars),
+                None if program.nested => Step::Ended(vars),
+                None => Step::Done(Err(ByteCodeError::UnknownByteCode), vars),
+            };
         };
-        let mut mpsc_data:  HashMap<&'static str, ByteCodeMpscSyncTypes> = HashMap::new();
-        for (_index, bc) in program.code.iter().enumerate() {
+        // a seeded scheduler may let other tasks run first wherever tasks interact
+        if program.resume.is_none() && switch_point(bc) && cx.preempt() {
+            program.resume = Some(Resume::Switched);
+            return Step::Yield;
+        }
+        {
             let bcr = match bc {
                 ByteCode::LoadVar(i) => {
                     program.stack.push(ByteCodeDataTypes::Integer64(*i));
                     None
                 },
-                ByteCode::WriteVar(var) => {
-                    if program.stack.is_empty() {
-                        return (Err(ByteCodeError::StackUnderflow), HashMap::new());
+                ByteCode::LoadU256(v) => {
+                    program.stack.push(ByteCodeDataTypes::U256(*v));
+                    None
+                },
+                ByteCode::LoadDecimal(v) => {
+                    program.stack.push(ByteCodeDataTypes::Decimal(*v));
+                    None
+                },
+                ByteCode::WriteVar(var) => {
+                    if program.stack.is_empty() {
+                        return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new());
+                    }
+                    program.global_vars.insert(*var, program.stack.pop().unwrap());
+                    None
+                },
+                ByteCode::ReadVar(var) => {
+                    if program.global_vars.contains_key(var) {
+                        program.stack.push(program.global_vars.get(var).unwrap().clone());
+                        None
+                    } else {
+                        return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new());
+                    }
+                },
+                ByteCode::Add => operation!(program, checked_add),
+                ByteCode::Subtract => operation!(program, checked_sub),
+                ByteCode::Multiply => operation!(program, checked_mul),
+                ByteCode::Divide => {
+                    if program.stack.last().is_some_and(ByteCodeDataTypes::is_zero) {
+                        Some(ByteCodeError::DivisionByZero)
+                    } else {
+                        operation!(program, checked_div)
+                    }
+                },
+                ByteCode::Modulo => {
+                    if program.stack.last().is_some_and(ByteCodeDataTypes::is_zero) {
+                        Some(ByteCodeError::DivisionByZero)
+                    } else {
+                        operation!(program, checked_rem)
+                    }
+                },
+                ByteCode::BitAnd => bitwise!(program, &, bitand),
+                ByteCode::BitOr => bitwise!(program, |, bitor),
+                ByteCode::BitXor => bitwise!(program, ^, bitxor),
+                ByteCode::BitNot => {
+                    match program.stack.pop() {
+                        Some(ByteCodeDataTypes::Integer64(v)) => {
+                            program.stack.push(ByteCodeDataTypes::Integer64(!v));
+                            None
+                        },
+                        Some(ByteCodeDataTypes::U256(v)) => {
+                            program.stack.push(ByteCodeDataTypes::U256(v.not()));
+                            None
+                        },
+                        Some(ByteCodeDataTypes::Boolean(v)) => {
+                            program.stack.push(ByteCodeDataTypes::Boolean(!v));
+                            None
+                        },
+                        Some(_) => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                        None => Some(ByteCodeError::StackUnderflow),
+                    }
+                },
+                ByteCode::ShiftLeft => shift!(program, <<, shl),
+                ByteCode::ShiftRight => shift!(program, >>, shr),
+                ByteCode::ToU256 => {
+                    match program.stack.pop() {
+                        Some(ByteCodeDataTypes::Integer64(v)) if v >= 0 => {
+                            program.stack.push(ByteCodeDataTypes::U256(U256::from_u64(v as u64)));
+                            None
+                        },
+                        Some(ByteCodeDataTypes::Integer64(_)) => return Step::Done(Err(ByteCodeError::Overflow), HashMap::new()),
+                        Some(ByteCodeDataTypes::U256(v)) => {
+                            program.stack.push(ByteCodeDataTypes::U256(v));
+                            None
+                        },
+                        Some(_) => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                        None => Some(ByteCodeError::StackUnderflow),
+                    }
+                },
+                ByteCode::ToI64 => {
+                    match program.stack.pop() {
+                        Some(ByteCodeDataTypes::U256(v)) => match v.to_i64() {
+                            Some(i) => {
+                                program.stack.push(ByteCodeDataTypes::Integer64(i));
+                                None
+                            },
+                            None => return Step::Done(Err(ByteCodeError::Overflow), HashMap::new()),
+                        },
+                        Some(ByteCodeDataTypes::Integer64(v)) => {
+                            program.stack.push(ByteCodeDataTypes::Integer64(v));
+                            None
+                        },
+                        Some(_) => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                        None => Some(ByteCodeError::StackUnderflow),
+                    }
+                },
+                ByteCode::MulDecimal(scale, mode) => decimal_operation!(program, mul_round, scale, mode),
+                ByteCode::DivDecimal(scale, mode) => {
+                    if program.stack.last().is_some_and(ByteCodeDataTypes::is_zero) {
+                        Some(ByteCodeError::DivisionByZero)
+                    } else {
+                        decimal_operation!(program, div_round, scale, mode)
+                    }
+                },
+                ByteCode::RoundDecimal(scale, mode) => {
+                    match program.stack.pop().as_ref().map(ByteCodeDataTypes::to_decimal) {
+                        Some(Some(v)) => match v.rescale(*scale, *mode) {
+                            Some(v) => {
+                                program.stack.push(ByteCodeDataTypes::Decimal(v));
+                                None
+                            },
+                            None => Some(ByteCodeError::Overflow),
+                        },
+                        Some(None) => Some(ByteCodeError::TypeMismatch),
+                        None => Some(ByteCodeError::StackUnderflow),
+                    }
+                },
+                ByteCode::LessThan => compare!(program, <),
+                ByteCode::LessThanEqual => compare!(program, <=),
+                ByteCode::GreaterThan => compare!(program, >),
+                ByteCode::GreaterThanEqual => compare!(program, >=),
+                ByteCode::Print(var) => {
+                    output!(format!("{} = {:?}", var, *program.global_vars.get(var).unwrap()));
+                    None
+                },
+                ByteCode::PrintLn(var) => {
+                    output_ln!(format!("{} = {:?}", var, *program.global_vars.get(var).unwrap()));
+                    None
+                },
+                ByteCode::ReturnValue => {
+                    return match program.stack.pop() {
+                        Some(res) => Step::Done(Ok(res), HashMap::new()),
+                        _ => Step::Done(Err(ByteCodeError::UnknownByteCode), HashMap::new()),
+                    }
+                },
+                ByteCode::Return => {
+                    return Step::Done(Ok(ByteCodeDataTypes::None), HashMap::new());
+                }
+                ByteCode::Unknown => {
+                    return Step::Done(Err(ByteCodeError::UnknownByteCode), HashMap::new());
+                },
+                ByteCode::Loop(loop_condition, _) => {
+                    // The condition and the loop code run as nested blocks, see block_finished
+                    program.resume = Some(Resume::LoopCondition);
+                    return Step::Call(program.block(loop_condition, Vec::new()));
+                },
+                ByteCode::FunctionCall(func_code, parameter_vars) => call_function!(program, func_code, Vec::new(), parameter_vars),
+                ByteCode::LoadFunction(func_code) => {
+                    if !program.functions.contains_key(func_code) {
+                        return Step::Done(Err(ByteCodeError::FunctionNotFound), HashMap::new());
+                    }
+                    program.stack.push(ByteCodeDataTypes::Function(FunctionValue { name: func_code, captured: Vec::new() }));
+                    None
+                },
+                ByteCode::Closure(func_code, captured_vars) => {
+                    if !program.functions.contains_key(func_code) {
+                        return Step::Done(Err(ByteCodeError::FunctionNotFound), HashMap::new());
+                    }
+                    let mut captured = Vec::new();
+                    for var in captured_vars {
+                        match program.global_vars.get(var) {
+                            Some(value) => captured.push((*var, value.clone())),
+                            None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                        }
+                    }
+                    program.stack.push(ByteCodeDataTypes::Function(FunctionValue { name: func_code, captured }));
+                    None
+                },
+                ByteCode::CallIndirect(parameter_vars) => {
+                    match program.stack.pop() {
+                        Some(ByteCodeDataTypes::Function(func)) => call_function!(program, func.name, func.captured, parameter_vars),
+                        Some(_) => return Step::Done(Err(ByteCodeError::NotAFunction), HashMap::new()),
+                        None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                    }
+                },
+                ByteCode::List(len) => {
+                    if program.stack.len() < *len {
+                        Some(ByteCodeError::StackUnderflow)
+                    } else {
+                        let items = program.stack.split_off(program.stack.len() - len);
+                        program.stack.push(ByteCodeDataTypes::List(items));
+                        None
+                    }
+                },
+                ByteCode::ListGet => {
+                    match (program.stack.pop(), program.stack.pop()) {
+                        (Some(ByteCodeDataTypes::Integer64(i)), Some(ByteCodeDataTypes::List(mut items))) => {
+                            if i >= 0 && (i as usize) < items.len() {
+                                program.stack.push(items.swap_remove(i as usize));
+                                None
+                            } else {
+                                return Step::Done(Err(ByteCodeError::IndexOutOfBounds), HashMap::new());
+                            }
+                        },
+                        (Some(_), Some(_)) => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                        _ => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                    }
+                },
+                ByteCode::ListPush => {
+                    match (program.stack.pop(), program.stack.pop()) {
+                        (Some(v), Some(ByteCodeDataTypes::List(mut items))) => {
+                            items.push(v);
+                            program.stack.push(ByteCodeDataTypes::List(items));
+                            None
+                        },
+                        (Some(_), Some(_)) => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                        _ => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                    }
+                },
+                ByteCode::ListLen => {
+                    match program.stack.pop() {
+                        Some(ByteCodeDataTypes::List(items)) => {
+                            program.stack.push(ByteCodeDataTypes::Integer64(items.len() as i64));
+                            None
+                        },
+                        Some(_) => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                        None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                    }
+                },
+                ByteCode::Sleep(duration) => {
+                    let deadline = program.deadline(cx.now(), Duration::from_secs(*duration));
+                    if cx.now() < deadline {
+                        cx.wait_until(deadline);
+                        cx.blocked_on("SLEEP", format!("{} seconds", duration));
+                        return Step::Pending;
+                    }
+                    None
+                },
+                ByteCode::SleepMs(duration) => {
+                    let deadline = program.deadline(cx.now(), Duration::from_millis(*duration));
+                    if cx.now() < deadline {
+                        cx.wait_until(deadline);
+                        cx.blocked_on("SLEEP_MS", format!("{} milliseconds", duration));
+                        return Step::Pending;
+                    }
+                    None
+                },
+                ByteCode::Now => {
+                    program.stack.push(ByteCodeDataTypes::Integer64(cx.clock().millis()));
+                    None
+                },
+                ByteCode::After(ms, crx) | ByteCode::Ticker(ms, crx) => {
+                    let period = matches!(bc, ByteCode::Ticker(..)).then(|| Duration::from_millis(*ms));
+                    let rx = ByteCodeDataTypes::Receiver(timer(cx.clock(), Duration::from_millis(*ms), period));
+                    match crx {
+                        Some(var) => {
+                            program.global_vars.insert(var, rx);
+                        },
+                        None => program.stack.push(rx),
+                    }
+                    None
+                },
+                ByteCode::Yield => {
+                    program.pc += 1;
+                    return Step::Yield;
+                },
+                ByteCode::Spawn(spawn_code, parameter_vars) => {
+                    // every other variable is copied, including channel end points that were not listed
+                    let parameters = move_parameters!(program.global_vars, parameter_vars);
+                    let mut vars_copy = program.global_vars.clone();
+                    vars_copy.extend(parameters);
+                    let task = program.spawn(Arc::new(spawn_code.to_vec()), Vec::new(), vars_copy);
+                    program.stack.push(ByteCodeDataTypes::Task(cx.spawn(task)));
+                    None
+                },
+                ByteCode::Supervise(policy, creports, supervised_code) => {
+                    let reports = match creports.map(|var| program.global_vars.get(var)) {
+                        Some(Some(ByteCodeDataTypes::Sender(tx))) => Some(tx.clone()),
+                        Some(_) => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
+                        None => None,
+                    };
+                    let mut task = program.spawn(Arc::new(supervised_code.to_vec()), Vec::new(), program.global_vars.clone());
+                    task.supervise(policy.clone(), reports);
+                    program.stack.push(ByteCodeDataTypes::Task(cx.spawn(task)));
+                    None
+                },
+                ByteCode::SpawnFunctions(count, argc) => {
+                    let mut tasks = Vec::new();
+                    for _ in 0..*count {
+                        let func = match program.stack.pop() {
+                            Some(ByteCodeDataTypes::Function(func)) => func,
+                            Some(_) => return Step::Done(Err(ByteCodeError::NotAFunction), HashMap::new()),
+                            None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                        };
+                        if program.stack.len() < *argc {
+                            return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new());
+                        }
+                        let args = program.stack.split_off(program.stack.len() - argc);
+                        tasks.push((func, args));
+                    }
+                    // Task handles are pushed in the order the functions were pushed
+                    for (func, args) in tasks.into_iter().rev() {
+                        let func_code = program.functions.get(func.name).unwrap().clone();
+                        let mut vars_copy = program.global_vars.clone();
+                        vars_copy.extend(func.captured);
+                        let task = program.spawn(func_code, args, vars_copy);
+                        program.stack.push(ByteCodeDataTypes::Task(cx.spawn(task)));
+                    }
+                    None
+                },
+                ByteCode::ParMap(func) | ByteCode::ParReduce(func) => {
+                    let (reduce, opcode) = match bc {
+                        ByteCode::ParReduce(_) => (true, "PAR_REDUCE"),
+                        _ => (false, "PAR_MAP"),
+                    };
+                    // handles of the running round and the values the next round starts with after their results
+                    let (func, mut handles, mut values) = match program.resume.take() {
+                        Some(Resume::Parallel(func, handles, values)) => (func, handles, values),
+                        _ => {
+                            let func = match func {
+                                Some(name) if program.functions.contains_key(name) => FunctionValue { name, captured: Vec::new() },
+                                Some(_) => return Step::Done(Err(ByteCodeError::FunctionNotFound), HashMap::new()),
+                                None => match program.stack.pop() {
+                                    Some(ByteCodeDataTypes::Function(func)) => func,
+                                    Some(_) => return Step::Done(Err(ByteCodeError::NotAFunction), HashMap::new()),
+                                    None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                                },
+                            };
+                            let items = match program.stack.pop() {
+                                Some(ByteCodeDataTypes::List(items)) => items,
+                                Some(_) => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                                None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                            };
+                            if reduce {
+                                (func, Vec::new(), items)
+                            } else {
+                                let handles = spawn_all(program, cx, &func, items.into_iter().map(|item| vec![item]).collect());
+                                (func, handles, Vec::new())
+                            }
+                        },
+                    };
+                    loop {
+                        let mut results = match join_in_order(&handles, cx, opcode) {
+                            Some(Ok(results)) => results,
+                            Some(Err(e)) => return Step::Done(Err(e), HashMap::new()),
+                            None => {
+                                program.resume = Some(Resume::Parallel(func, handles, values));
+                                return Step::Pending;
+                            },
+                        };
+                        if !reduce {
+                            program.stack.push(ByteCodeDataTypes::List(results));
+                            break;
+                        }
+                        // Neighbours are combined in rounds, an odd value out is kept at the end for the next round
+                        results.append(&mut values);
+                        if results.len() < 2 {
+                            program.stack.push(results.pop().unwrap_or(ByteCodeDataTypes::None));
+                            break;
+                        }
+                        if results.len() % 2 == 1 {
+                            values.push(results.pop().unwrap());
+                        }
+                        handles = spawn_all(program, cx, &func, results.chunks(2).map(|pair| pair.to_vec()).collect());
+                    }
+                    None
+                },
+                ByteCode::Join(var) => {
+                    match named_or_popped!(program, var) {
+                        Some(ByteCodeDataTypes::Task(handle)) => match handle.poll_join(Some(cx.waker())) {
+                            Some(Ok(r)) => program.stack.push(r),
+                            Some(Err(e)) => {
+                                return Step::Done(Err(e), HashMap::new());
+                            },
+                            None => {
+                                cx.blocked_on("JOIN", format!("{:?}", handle));
+                                if var.is_none() {
+                                    program.stack.push(ByteCodeDataTypes::Task(handle));
+                                }
+                                return Step::Pending;
+                            },
+                        },
+                        Some(_) => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                        None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                    }
+                    None
+                },
+                ByteCode::Cancel(var) => {
+                    match named_or_popped!(program, var) {
+                        Some(ByteCodeDataTypes::Task(handle)) => handle.cancel(),
+                        Some(_) => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                        None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                    }
+                    None
+                },
+                ByteCode::SelfTask => {
+                    program.stack.push(ByteCodeDataTypes::Task(cx.task().clone()));
+                    None
+                },
+                ByteCode::SendTo(pid) => {
+                    let handle = match named_or_popped!(program, pid) {
+                        Some(ByteCodeDataTypes::Task(handle)) => handle,
+                        Some(_) => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                        None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                    };
+                    // mailboxes are unbounded, sending never waits
+                    match program.stack.pop() {
+                        Some(v) => {
+                            if let Err(e) = handle.mailbox_sender().try_send(v) {
+                                return Step::Done(Err(e), HashMap::new());
+                            }
+                        },
+                        None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                    }
+                    None
+                },
+                ByteCode::Receive => {
+                    match cx.task().mailbox().poll_recv(Some(cx.waker())) {
+                        Some(Ok(v)) => program.stack.push(v),
+                        Some(Err(e)) => return Step::Done(Err(e), HashMap::new()),
+                        None => {
+                            cx.blocked_on("RECEIVE", format!("mailbox of {:?}", cx.task()));
+                            return Step::Pending;
+                        },
+                    }
+                    None
+                },
+                ByteCode::JoinAll => {
+                    match program.stack.pop() {
+                        Some(ByteCodeDataTypes::List(handles)) => {
+                            let mut results = Vec::new();
+                            for handle in handles.iter() {
+                                match handle {
+                                    ByteCodeDataTypes::Task(handle) => match handle.poll_join(Some(cx.waker())) {
+                                        Some(Ok(r)) => results.push(r),
+                                        Some(Err(e)) => {
+                                            return Step::Done(Err(e), HashMap::new());
+                                        },
+                                        None => break,
+                                    },
+                                    _ => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                                }
+                            }
+                            if results.len() < handles.len() {
+                                cx.blocked_on("JOIN_ALL", format!("{:?}", handles[results.len()]));
+                                program.stack.push(ByteCodeDataTypes::List(handles));
+                                return Step::Pending;
+                            }
+                            program.stack.push(ByteCodeDataTypes::List(results));
+                        },
+                        Some(_) => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                        None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                    }
+                    None
+                },
+                ByteCode::Mutex(var) => {
+                    match program.stack.pop() {
+                        Some(v) => {
+                            program.global_vars.insert(var, ByteCodeDataTypes::Mutex(SharedMutex::new(v)));
+                        },
+                        None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                    }
+                    None
+                },
+                ByteCode::WithLock(var, lock_code) => {
+                    let mutex = match program.global_vars.get(var) {
+                        Some(ByteCodeDataTypes::Mutex(mutex)) => mutex.clone(),
+                        _ => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                    };
+                    let (guard, value) = match mutex.poll_lock(Some(cx.waker())) {
+                        Some(Ok(locked)) => locked,
+                        Some(Err(e)) => return Step::Done(Err(e), HashMap::new()),
+                        None => {
+                            cx.blocked_on("WITH_LOCK", format!("{:?}", mutex));
+                            return Step::Pending;
+                        },
+                    };
+                    let mut block = program.block(lock_code, Vec::new());
+                    block.global_vars.insert(var, value.clone());
+                    program.resume = Some(Resume::Lock(guard, value, mutex));
+                    return Step::Call(block);
+                },
+                ByteCode::Atomic(var) => {
+                    match program.stack.pop() {
+                        Some(ByteCodeDataTypes::Integer64(v)) => {
+                            program.global_vars.insert(var, ByteCodeDataTypes::Atomic(AtomicCell::new(v)));
+                            None
+                        },
+                        Some(_) => Some(ByteCodeError::TypeMismatch),
+                        None => Some(ByteCodeError::StackUnderflow),
+                    }
+                },
+                ByteCode::AtomicAdd(var) => {
+                    let cell = atomic_var!(program, var);
+                    match program.stack.pop() {
+                        Some(ByteCodeDataTypes::Integer64(delta)) => {
+                            program.stack.push(ByteCodeDataTypes::Integer64(cell.fetch_add(delta)));
+                            None
+                        },
+                        Some(_) => Some(ByteCodeError::TypeMismatch),
+                        None => Some(ByteCodeError::StackUnderflow),
+                    }
+                },
+                ByteCode::AtomicLoad(var) => {
+                    let cell = atomic_var!(program, var);
+                    program.stack.push(ByteCodeDataTypes::Integer64(cell.load()));
+                    None
+                },
+                ByteCode::AtomicStore(var) => {
+                    let cell = atomic_var!(program, var);
+                    match program.stack.pop() {
+                        Some(ByteCodeDataTypes::Integer64(v)) => {
+                            cell.store(v);
+                            None
+                        },
+                        Some(_) => Some(ByteCodeError::TypeMismatch),
+                        None => Some(ByteCodeError::StackUnderflow),
+                    }
+                },
+                ByteCode::CompareAndSwap(var) => {
+                    let cell = atomic_var!(program, var);
+                    match (program.stack.pop(), program.stack.pop()) {
+                        (Some(ByteCodeDataTypes::Integer64(new)), Some(ByteCodeDataTypes::Integer64(expected))) => {
+                            program.stack.push(ByteCodeDataTypes::Boolean(cell.compare_and_swap(expected, new)));
+                            None
+                        },
+                        (Some(_), Some(_)) => Some(ByteCodeError::TypeMismatch),
+                        _ => Some(ByteCodeError::StackUnderflow),
+                    }
+                },
+                ByteCode::WaitGroup(var) => {
+                    program.global_vars.insert(var, ByteCodeDataTypes::WaitGroup(WaitGroup::new()));
+                    None
+                },
+                ByteCode::WaitGroupAdd(delta, var) => {
+                    if let Err(e) = wait_group_var!(program, var).add(*delta) {
+                        return Step::Done(Err(e), HashMap::new());
+                    }
+                    None
+                },
+                ByteCode::WaitGroupDone(var) => {
+                    if let Err(e) = wait_group_var!(program, var).done() {
+                        return Step::Done(Err(e), HashMap::new());
+                    }
+                    None
+                },
+                ByteCode::WaitGroupWait(var) => {
+                    let wg = wait_group_var!(program, var);
+                    if wg.poll_wait(Some(cx.waker())).is_none() {
+                        cx.blocked_on("WG_WAIT", format!("{:?}", wg));
+                        return Step::Pending;
+                    }
+                    None
+                },
+                ByteCode::Barrier(parties, var) => {
+                    program.global_vars.insert(var, ByteCodeDataTypes::Barrier(Barrier::new(*parties)));
+                    None
+                },
+                ByteCode::BarrierWait(var) => {
+                    let barrier = barrier_var!(program, var);
+                    // the task arrives once, after being woken it only checks whether its round is over
+                    let generation = match program.resume.take() {
+                        Some(Resume::Barrier(generation)) => Some(generation),
+                        _ => barrier.arrive(),
+                    };
+                    if let Some(generation) = generation {
+                        if barrier.poll_passed(generation, Some(cx.waker())).is_none() {
+                            cx.blocked_on("BARRIER_WAIT", format!("{:?}", barrier));
+                            program.resume = Some(Resume::Barrier(generation));
+                            return Step::Pending;
+                        }
                     }
-                    program.global_vars.insert(*var, program.stack.pop().unwrap());
                     None
                 },
-                ByteCode::ReadVar(var) => {
-                    if program.global_vars.contains_key(var) {
-                        program.stack.push(program.global_vars.get(var).unwrap().clone());
-                        None
-                    } else {
-                        return (Err(ByteCodeError::StackUnderflow), HashMap::new());
+                ByteCode::Channel(capacity, vars) => {
+                    let (tx, rx) = channel(*capacity);
+                    match vars {
+                        Some((ctx, crx)) => {
+                            program.global_vars.insert(ctx, ByteCodeDataTypes::Sender(tx));
+                            program.global_vars.insert(crx, ByteCodeDataTypes::Receiver(rx));
+                        },
+                        None => {
+                            program.stack.push(ByteCodeDataTypes::Sender(tx));
+                            program.stack.push(ByteCodeDataTypes::Receiver(rx));
+                        },
                     }
+                    None
                 },
-                ByteCode::Add => operation!(program, +),
-                ByteCode::Subtract => operation!(program, -),
-                ByteCode::Multiply => operation!(program, *),
-                ByteCode::Divide => {
-                    if program.stack[program.stack.len()-1] == ByteCodeDataTypes::Integer64(0) {
-                        Some(ByteCodeError::DivisionByZero)
-                    } else {
-                        operation!(program, /)
+                ByteCode::SendChannel(ctx) => {
+                    match named_or_popped!(program, ctx) {
+                        Some(ByteCodeDataTypes::Sender(tx)) => {
+                            let mut value = program.stack.pop();
+                            if value.is_none() {
+                                return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new());
+                            }
+                            match tx.poll_send(&mut value, Some(cx.waker())) {
+                                Some(Ok(())) => {},
+                                Some(Err(e)) => return Step::Done(Err(e), HashMap::new()),
+                                None => {
+                                    // operands are popped again when the task is woken
+                                    cx.blocked_on("SEND_CHANNEL", format!("{:?}", tx));
+                                    program.stack.extend(value);
+                                    if ctx.is_none() {
+                                        program.stack.push(ByteCodeDataTypes::Sender(tx));
+                                    }
+                                    return Step::Pending;
+                                },
+                            }
+                        },
+                        _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
                     }
+                    None
                 },
-                ByteCode::LessThan => compare!(program, <),
-                ByteCode::LessThanEqual => compare!(program, <=),
-                ByteCode::GreaterThan => compare!(program, >),
-                ByteCode::GreaterThanEqual => compare!(program, >=),
-                ByteCode::Print(var) => {
-                    output!(format!("{} = {:?}", var, *program.global_vars.get(var).unwrap()));
+                ByteCode::ReceiveChannel(crx) => {
+                    match named_or_popped!(program, crx) {
+                        Some(ByteCodeDataTypes::Receiver(rx)) => {
+                            match rx.poll_recv(Some(cx.waker())) {
+                                Some(Ok(v)) => program.stack.push(v),
+                                Some(Err(e)) => return Step::Done(Err(e), HashMap::new()),
+                                None => {
+                                    wait_receiving(cx, "RECEIVE_CHANNEL", &rx);
+                                    if crx.is_none() {
+                                        program.stack.push(ByteCodeDataTypes::Receiver(rx));
+                                    }
+                                    return Step::Pending;
+                                },
+                            }
+                        },
+                        _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
+                    }
                     None
                 },
-                ByteCode::PrintLn(var) => {
-                    output_ln!(format!("{} = {:?}", var, *program.global_vars.get(var).unwrap()));
+                ByteCode::TrySend(ctx) => {
+                    match named_or_popped!(program, ctx) {
+                        Some(ByteCodeDataTypes::Sender(tx)) => {
+                            match program.stack.pop() {
+                                Some(v) => match tx.try_send(v) {
+                                    Ok(sent) => program.stack.push(ByteCodeDataTypes::Boolean(sent)),
+                                    Err(e) => return Step::Done(Err(e), HashMap::new()),
+                                },
+                                None => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                            }
+                        },
+                        _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
+                    }
                     None
                 },
-                ByteCode::ReturnValue => {
-                    return match program.stack.pop() {
-                        Some(res) => (Ok(res), HashMap::new()),
-                        _ => (Err(ByteCodeError::UnknownByteCode), HashMap::new()),
+                ByteCode::TryReceive(crx) => {
+                    match named_or_popped!(program, crx) {
+                        Some(ByteCodeDataTypes::Receiver(rx)) => push_received!(program, rx.try_recv()),
+                        _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
                     }
+                    None
                 },
-                ByteCode::Return => {
-                    return (Ok(ByteCodeDataTypes::None), HashMap::new());
-                }
-                ByteCode::Loop(loop_condition, loop_code) => {
-                    loop {
-                        let (result, vars) = execute(loop_condition.to_vec(),
-                                                     Vec::new(),
-                                                     program.global_vars.clone(),
-                                                     HashMap::new(),
-                                                     program.functions.clone());
-                        program.global_vars = vars;
-                        match result {
-                            Ok(r) => match r {
-                                ByteCodeDataTypes::Boolean(b) => {
-                                    if b {
-                                        let (_result, vars) = execute(loop_code.to_vec(),
-                                                                      Vec::new(),
-                                                                      program.global_vars.clone(),
-                                                                      HashMap::new(),
-                                                                      program.functions.clone());
-                                        program.global_vars = vars;
-                                    } else {
-                                        break;
+                ByteCode::ReceiveTimeout(timeout, crx) => {
+                    match named_or_popped!(program, crx) {
+                        Some(ByteCodeDataTypes::Receiver(rx)) => {
+                            let deadline = program.deadline(cx.now(), Duration::from_millis(*timeout));
+                            match rx.poll_recv(Some(cx.waker())) {
+                                Some(received) => push_received!(program, received.map(Some)),
+                                None if cx.now() >= deadline => push_received!(program, Ok(None)),
+                                None => {
+                                    wait_receiving(cx, "RECEIVE_TIMEOUT", &rx);
+                                    if crx.is_none() {
+                                        program.stack.push(ByteCodeDataTypes::Receiver(rx));
                                     }
+                                    cx.wait_until(deadline);
+                                    return Step::Pending;
                                 },
-                                _ => {}
-                            },
-                            Err(e) => {
-                                return (Err(e), HashMap::new());
                             }
-                        }
+                        },
+                        _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
                     }
                     None
                 },
-                ByteCode::FunctionCall(func_code, parameter_vars) => {
-                    let parameters = move_parameters!(program.parameters, parameter_vars);
-                    let (result, _vars) = execute((*program.functions.get(func_code).unwrap().to_vec()).to_owned(),
-                                                  Vec::new(),
-                                                  program.global_vars.clone(),
-                                                  parameters,
-                                                  program.functions.clone());
-                    match result {
-                        Ok(r) => program.stack.push(r),
-                        Err(e) => {
-                            return (Err(e), HashMap::new());
-                        }
+                ByteCode::CloneSender(ctx) => {
+                    match named_or_popped!(program, ctx) {
+                        Some(ByteCodeDataTypes::Sender(tx)) => {
+                            if ctx.is_none() {
+                                program.stack.push(ByteCodeDataTypes::Sender(tx.clone()));
+                            }
+                            program.stack.push(ByteCodeDataTypes::Sender(tx));
+                        },
+                        _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
                     }
                     None
                 },
-                ByteCode::Sleep(duration) => {
-                    thread::sleep(Duration::from_secs(*duration));
+                ByteCode::Broadcast(var) => {
+                    let tx = ByteCodeDataTypes::Sender(broadcast());
+                    match var {
+                        Some(var) => {
+                            program.global_vars.insert(var, tx);
+                        },
+                        None => program.stack.push(tx),
+                    }
                     None
                 },
-                ByteCode::Spawn(spawn_code, parameter_vars) => {
-                    let spawn_code_copy = spawn_code.to_vec();
-                    let vars_copy = program.global_vars.clone();
-                    let functions_copy = program.functions.clone();
-                    let (tx, rx) = mpsc::sync_channel(1);
-                    let parameters = move_parameters!(mpsc_data, parameter_vars);
-                    thread::spawn(move || {
-                        let result = execute(spawn_code_copy,
-                                             Vec::new(),
-                                             vars_copy,
-                                             parameters,
-                                             functions_copy);
-                        tx.send(result).unwrap();
-                    });
-                    let (result, _vars) = rx.recv().unwrap();
-                    match result {
-                        Ok(r) => program.stack.push(r),
-                        Err(e) => {
-                            return (Err(e), HashMap::new());
-                        }
+                ByteCode::Subscribe(ctx) => {
+                    match named_or_popped!(program, ctx) {
+                        Some(ByteCodeDataTypes::Sender(tx)) => match tx.subscribe() {
+                            Some(rx) => program.stack.push(ByteCodeDataTypes::Receiver(rx)),
+                            None => return Step::Done(Err(ByteCodeError::TypeMismatch), HashMap::new()),
+                        },
+                        _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
                     }
                     None
                 },
-                ByteCode::Channel(ctx, crx) => {
-                    let (tx, rx): (Sender<i64>, Receiver<i64>) = channel();
-                    mpsc_data.insert(ctx, ByteCodeMpscSyncTypes::SendChannel(tx));
-                    mpsc_data.insert(crx, ByteCodeMpscSyncTypes::ReceiveChannel(rx));
-                    //output_ln!(format!("ByteCode::Channel- {:?}", mpsc_data));
+                ByteCode::CloseChannel(ch) => {
+                    match named_or_popped!(program, ch) {
+                        Some(ByteCodeDataTypes::Sender(tx)) => tx.close(),
+                        Some(ByteCodeDataTypes::Receiver(rx)) => rx.close(),
+                        _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
+                    }
                     None
                 },
-                ByteCode::SendChannel(ctx) => {
-                    if program.parameters.contains_key(ctx) {
-                        match program.parameters.get(ctx).unwrap() {
-                            ByteCodeMpscSyncTypes::SendChannel(tx) => {
-                                match program.stack.pop().unwrap() {
-                                    ByteCodeDataTypes::Integer64(v) => {
-                                        tx.send(v).unwrap()
-                                    },
-                                    _ => return (Err(ByteCodeError::UnknownByteCode), HashMap::new()),
-                                }
-                            },
-                            _ => return (Err(ByteCodeError::ChannelNotFound), HashMap::new()),
-                        }
-                    } else {
-                        return (Err(ByteCodeError::ChannelNotFound), HashMap::new());
+                ByteCode::IsClosed(ch) => {
+                    match named_or_popped!(program, ch) {
+                        Some(ByteCodeDataTypes::Sender(tx)) => program.stack.push(ByteCodeDataTypes::Boolean(tx.is_closed())),
+                        Some(ByteCodeDataTypes::Receiver(rx)) => program.stack.push(ByteCodeDataTypes::Boolean(rx.is_closed())),
+                        _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
                     }
                     None
                 },
-                ByteCode::ReceiveChannel(crx) => {
-                    if program.parameters.contains_key(crx) {
-                        match program.parameters.get(crx).unwrap() {
-                            ByteCodeMpscSyncTypes::ReceiveChannel(rx) => {
-                                let received = rx.recv().unwrap();
-                                program.stack.push(ByteCodeDataTypes::Integer64(received));
+                ByteCode::ReceiveLoop(crx, loop_code) => {
+                    let rx = match program.resume.take() {
+                        Some(Resume::Receive(rx)) => rx,
+                        _ => match program.global_vars.get(crx) {
+                            Some(ByteCodeDataTypes::Receiver(rx)) => rx.clone(),
+                            _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
+                        },
+                    };
+                    // Runs the body for every received value until the channel is closed and drained
+                    match rx.poll_recv(Some(cx.waker())) {
+                        Some(Ok(v)) => {
+                            let block = program.block(loop_code, vec![v]);
+                            program.resume = Some(Resume::Receive(rx));
+                            return Step::Call(block);
+                        },
+                        Some(Err(ByteCodeError::ChannelClosed)) => {},
+                        Some(Err(e)) => return Step::Done(Err(e), HashMap::new()),
+                        None => {
+                            wait_receiving(cx, "RECEIVE_LOOP", &rx);
+                            program.resume = Some(Resume::Receive(rx));
+                            return Step::Pending;
+                        },
+                    }
+                    None
+                },
+                ByteCode::Select(arms) => {
+                    let mut ops = Vec::new();
+                    let mut op_arms = Vec::new();
+                    let mut timeout = None;
+                    let mut default = None;
+                    for (i, (case, _)) in arms.iter().enumerate() {
+                        match case {
+                            SelectCase::Receive(crx) => match program.global_vars.get(crx) {
+                                Some(ByteCodeDataTypes::Receiver(rx)) => ops.push(SelectOp::Receive(rx.clone())),
+                                _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
+                            },
+                            SelectCase::Send(ctx, var) => match (program.global_vars.get(ctx), program.global_vars.get(var)) {
+                                (Some(ByteCodeDataTypes::Sender(tx)), Some(v)) => ops.push(SelectOp::Send(tx.clone(), Some(v.clone()))),
+                                (Some(ByteCodeDataTypes::Sender(_)), None) => return Step::Done(Err(ByteCodeError::StackUnderflow), HashMap::new()),
+                                _ => return Step::Done(Err(ByteCodeError::ChannelNotFound), HashMap::new()),
+                            },
+                            SelectCase::Timeout(ms) => {
+                                if timeout.is_none_or(|(t, _)| *ms < t) {
+                                    timeout = Some((*ms, i));
+                                }
+                                continue;
+                            },
+                            SelectCase::Default => {
+                                default = Some(i);
+                                continue;
                             },
-                            _ => return (Err(ByteCodeError::ChannelNotFound), HashMap::new()),
                         }
-                    } else {
-                        return (Err(ByteCodeError::ChannelNotFound), HashMap::new());
+                        op_arms.push(i);
                     }
-                    None
-                }
-                _ => Some(ByteCodeError::StackUnderflow),
+                    let deadline = timeout.map(|(ms, _)| program.deadline(cx.now(), Duration::from_millis(ms)));
+                    // The chosen arm runs like a loop body, a received value is on its stack.
+                    // A receive arm on a closed and drained channel is never ready again, sending to a closed channel fails
+                    let waker = default.is_none().then(|| cx.waker());
+                    let start = cx.choose(ops.len());
+                    let mut closed = false;
+                    let selected = loop {
+                        match try_select(&mut ops, start, waker) {
+                            Some((op, Err(ByteCodeError::ChannelClosed))) if matches!(ops[op], SelectOp::Receive(_)) => {
+                                ops.remove(op);
+                                op_arms.remove(op);
+                                closed = true;
+                            },
+                            selected => break selected,
+                        }
+                    };
+                    let (arm, stack) = match selected {
+                        Some((op, Ok(received))) => (op_arms[op], received.into_iter().collect()),
+                        Some((_, Err(e))) => return Step::Done(Err(e), HashMap::new()),
+                        // every arm is closed, nothing could make the select ready
+                        None if closed && ops.is_empty() && default.is_none() && timeout.is_none() => {
+                            return Step::Done(Err(ByteCodeError::ChannelClosed), HashMap::new());
+                        },
+                        None => match (default, timeout, deadline) {
+                            (Some(arm), _, _) => (arm, Vec::new()),
+                            (None, Some((_, arm)), Some(deadline)) if cx.now() >= deadline => (arm, Vec::new()),
+                            (None, _, deadline) => {
+                                if let Some(deadline) = deadline {
+                                    cx.wait_until(deadline);
+                                }
+                                let channels: Vec<String> = ops.iter().map(|op| match op {
+                                    SelectOp::Receive(rx) => {
+                                        if let Some(deadline) = rx.deadline() {
+                                            cx.wait_until(deadline);
+                                        }
+                                        format!("{:?}", rx)
+                                    },
+                                    SelectOp::Send(tx, _) => format!("{:?}", tx),
+                                }).collect();
+                                cx.blocked_on("SELECT", channels.join(", "));
+                                return Step::Pending;
+                            },
+                        },
+                    };
+                    let block = program.block(&arms[arm].1, stack);
+                    program.resume = Some(Resume::Block);
+                    return Step::Call(block);
+                },
             };
             match bcr {
                 Some(err) => output_ln!(format!("{:?}", err)),
                 _ => {},
             }
         }
-        return match program.stack.pop() {
-            Some(res) => (Ok(res), program.global_vars),
-            _ => (Err(ByteCodeError::UnknownByteCode), program.global_vars),
-        };
+        program.resume = None;
+        program.pc += 1;
+        Step::Next
     }
 }
 #[cfg(test)]
 mod tests {
-    use crate::{output_ln, interpreter::interpreter::{execute, ByteCode}};
+    use crate::{output_ln, interpreter::interpreter::{execute, ByteCode, ByteCodeDataTypes, ByteCodeError, SelectCase, Supervision}};
+    use crate::u256::u256::U256;
+    use crate::decimal::decimal::RoundingMode;
+    use crate::mutex::mutex::SharedMutex;
+    use crate::runtime::runtime::Vm;
     use std::collections::HashMap;
     use std::fs;
     use std::fs::metadata;
@@ -369,6 +1713,23 @@ mod tests {
         };
     }
     /*
+    Macro to print result or error to terminal and compare result with expected value
+     */
+    macro_rules! result_eq {
+        ($func:expr, $result:expr, $expected:expr) => {
+            match $result {
+                Ok(res) => {
+                    output_ln!(format!("{}, Return Value: {:?}", $func, res));
+                    res == $expected
+                },
+                Err(e) => {
+                    output_ln!(format!("{} Error: {:?}", $func, e));
+                    false
+                },
+            }
+        };
+    }
+    /*
     Test arithmetic operation
      */
     fn execute_arithmetic_byte_code() -> bool {
@@ -380,7 +1741,6 @@ mod tests {
                          ByteCode::Multiply, ByteCode::ReturnValue],
                     Vec::new(),
                     HashMap::new(),
-                    HashMap::new(),
                     HashMap::new());
         result!("execute_arithmetic_byte_code", result)
     }
@@ -396,7 +1756,6 @@ mod tests {
                          ByteCode::GreaterThan, ByteCode::ReturnValue],
                     Vec::new(),
                     HashMap::new(),
-                    HashMap::new(),
                     HashMap::new());
         result!("execute_compare_byte_code", result)
     }
@@ -412,15 +1771,251 @@ mod tests {
                          ByteCode::FunctionCall("add", vec![]), ByteCode::ReturnValue],
                     Vec::new(),
                     HashMap::new(),
-                    HashMap::new(),
                     functions);
         result!("execute_function_byte_code", result)
     }
     /*
+    Test U256 arithmetic, bitwise operations and conversion back to Integer64
+     */
+    fn execute_u256_byte_code() -> bool {
+        let (result, _) =
+            execute(vec![ByteCode::LoadU256(U256::ZERO.not()), ByteCode::LoadVar(255), ByteCode::ToU256,
+                         ByteCode::BitXor, ByteCode::LoadVar(248), ByteCode::ShiftRight,
+                         ByteCode::LoadU256(U256::from(3)), ByteCode::Multiply, ByteCode::ToI64,
+                         ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    HashMap::new());
+        result_eq!("execute_u256_byte_code", result, ByteCodeDataTypes::Integer64(765))
+    }
+    /*
+    Test decimal arithmetic with explicit rounding mode
+     */
+    fn execute_decimal_byte_code() -> bool {
+        let (result, _) =
+            execute(vec![ByteCode::LoadDecimal("10.00".parse().unwrap()), ByteCode::LoadVar(3), ByteCode::Divide,
+                         ByteCode::LoadDecimal("0.5".parse().unwrap()), ByteCode::Subtract,
+                         ByteCode::RoundDecimal(1, RoundingMode::Ceiling), ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    HashMap::new());
+        result_eq!("execute_decimal_byte_code", result, ByteCodeDataTypes::Decimal("2.9".parse().unwrap()))
+    }
+    /*
+    Test function reference stored in list and called indirectly
+     */
+    fn execute_function_value_byte_code() -> bool {
+        let mut functions = HashMap::new();
+        functions.insert("add", vec![ByteCode::ReadVar("x"), ByteCode::ReadVar("y"), ByteCode::Add, ByteCode::ReturnValue]);
+        let (result, _) =
+            execute(vec![ByteCode::LoadVar(1), ByteCode::WriteVar("x"),
+                         ByteCode::LoadVar(2), ByteCode::WriteVar("y"),
+                         ByteCode::LoadFunction("add"), ByteCode::LoadVar(7), ByteCode::List(2),
+                         ByteCode::LoadVar(0), ByteCode::ListGet, ByteCode::CallIndirect(vec![]),
+                         ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    functions);
+        result_eq!("execute_function_value_byte_code", result, ByteCodeDataTypes::Integer64(3))
+    }
+    /*
+    Test closure keeps the captured value even when the variable changes afterwards
+     */
+    fn execute_closure_byte_code() -> bool {
+        let mut functions = HashMap::new();
+        functions.insert("add", vec![ByteCode::ReadVar("x"), ByteCode::ReadVar("y"), ByteCode::Add, ByteCode::ReturnValue]);
+        let (result, _) =
+            execute(vec![ByteCode::LoadVar(10), ByteCode::WriteVar("y"),
+                         ByteCode::Closure("add", vec!["y"]), ByteCode::WriteVar("add_ten"),
+                         ByteCode::LoadVar(100), ByteCode::WriteVar("y"),
+                         ByteCode::LoadVar(5), ByteCode::WriteVar("x"),
+                         ByteCode::ReadVar("add_ten"), ByteCode::CallIndirect(vec![]),
+                         ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    functions);
+        result_eq!("execute_closure_byte_code", result, ByteCodeDataTypes::Integer64(15))
+    }
+    /*
+    Test spawning function values popped from stack with their arguments
+     */
+    fn execute_spawn_functions_byte_code() -> bool {
+        let mut functions = HashMap::new();
+        functions.insert("scale", vec![ByteCode::WriteVar("x"), ByteCode::ReadVar("x"), ByteCode::ReadVar("factor"),
+                                       ByteCode::Multiply, ByteCode::ReturnValue]);
+        let (result, _) =
+            execute(vec![ByteCode::LoadVar(3), ByteCode::WriteVar("factor"),
+                         ByteCode::LoadVar(1), ByteCode::Closure("scale", vec!["factor"]),
+                         ByteCode::LoadVar(10), ByteCode::WriteVar("factor"),
+                         ByteCode::LoadVar(2), ByteCode::LoadFunction("scale"),
+                         ByteCode::SpawnFunctions(2, 1), ByteCode::List(2), ByteCode::JoinAll, ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    functions);
+        result_eq!("execute_spawn_functions_byte_code", result,
+                   ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Integer64(3), ByteCodeDataTypes::Integer64(20)]))
+    }
+    /*
+    Test channel end points stored in variables and lists and used from stack
+     */
+    fn execute_channel_value_byte_code() -> bool {
+        let (result, _) =
+            execute(vec![ByteCode::Channel(None, None), ByteCode::WriteVar("rx"), ByteCode::List(1), ByteCode::WriteVar("senders"),
+                         ByteCode::LoadVar(7), ByteCode::ReadVar("senders"), ByteCode::LoadVar(0), ByteCode::ListGet,
+                         ByteCode::SendChannel(None), ByteCode::ReadVar("rx"), ByteCode::ReceiveChannel(None),
+                         ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    HashMap::new());
+        result_eq!("execute_channel_value_byte_code", result, ByteCodeDataTypes::Integer64(7))
+    }
+    /*
+    Test values of different types sent through a channel keep their type
+     */
+    fn execute_channel_types_byte_code() -> bool {
+        let (result, _) =
+            execute(vec![ByteCode::Channel(None, Some(("tx", "rx"))),
+                         ByteCode::LoadVar(1), ByteCode::LoadVar(2), ByteCode::LessThan, ByteCode::SendChannel(Some("tx")),
+                         ByteCode::LoadDecimal("0.25".parse().unwrap()), ByteCode::List(1), ByteCode::SendChannel(Some("tx")),
+                         ByteCode::ReceiveChannel(Some("rx")), ByteCode::ReceiveChannel(Some("rx")), ByteCode::List(2),
+                         ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    HashMap::new());
+        result_eq!("execute_channel_types_byte_code", result,
+                   ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Boolean(true),
+                                                ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Decimal("0.25".parse().unwrap())])]))
+    }
+    /*
+    Test non blocking send and receive on bounded channel
+     */
+    fn execute_bounded_channel_byte_code() -> bool {
+        let (result, _) =
+            execute(vec![ByteCode::Channel(Some(1), Some(("tx", "rx"))),
+                         ByteCode::LoadVar(1), ByteCode::TrySend(Some("tx")),
+                         ByteCode::LoadVar(2), ByteCode::TrySend(Some("tx")),
+                         ByteCode::ReceiveTimeout(10, Some("rx")), ByteCode::TryReceive(Some("rx")),
+                         ByteCode::List(6), ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    HashMap::new());
+        result_eq!("execute_bounded_channel_byte_code", result,
+                   ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Boolean(true), ByteCodeDataTypes::Boolean(false),
+                                                ByteCodeDataTypes::Integer64(1), ByteCodeDataTypes::Boolean(true),
+                                                ByteCodeDataTypes::None, ByteCodeDataTypes::Boolean(false)]))
+    }
+    /*
+    Test select picks the ready receive arm, the default arm and the timeout arm
+     */
+    fn execute_select_byte_code() -> bool {
+        let receive_arms = vec![
+            (SelectCase::Receive("rx1"), vec![ByteCode::LoadVar(1), ByteCode::Add, ByteCode::WriteVar("picked")]),
+            (SelectCase::Receive("rx2"), vec![ByteCode::LoadVar(2), ByteCode::Add, ByteCode::WriteVar("picked")]),
+        ];
+        let mut default_arms = receive_arms.clone();
+        default_arms.push((SelectCase::Default, vec![ByteCode::LoadVar(-1), ByteCode::WriteVar("picked")]));
+        let mut timeout_arms = receive_arms.clone();
+        timeout_arms.push((SelectCase::Timeout(10), vec![ByteCode::LoadVar(-2), ByteCode::WriteVar("picked")]));
+        let (result, _) =
+            execute(vec![ByteCode::Channel(None, Some(("tx1", "rx1"))), ByteCode::Channel(None, Some(("tx2", "rx2"))),
+                         ByteCode::LoadVar(20), ByteCode::SendChannel(Some("tx2")),
+                         ByteCode::Select(receive_arms), ByteCode::ReadVar("picked"),
+                         ByteCode::Select(default_arms), ByteCode::ReadVar("picked"),
+                         ByteCode::Select(timeout_arms), ByteCode::ReadVar("picked"),
+                         ByteCode::List(3), ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    HashMap::new());
+        result_eq!("execute_select_byte_code", result,
+                   ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Integer64(22), ByteCodeDataTypes::Integer64(-1),
+                                                ByteCodeDataTypes::Integer64(-2)]))
+    }
+    /*
+    Test values sent before close are received and the channel reports closed
+     */
+    fn execute_channel_close_byte_code() -> bool {
+        let (result, _) =
+            execute(vec![ByteCode::Channel(None, Some(("tx", "rx"))),
+                         ByteCode::LoadVar(1), ByteCode::SendChannel(Some("tx")), ByteCode::CloseChannel(Some("tx")),
+                         ByteCode::IsClosed(Some("rx")), ByteCode::ReceiveChannel(Some("rx")), ByteCode::List(2),
+                         ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    HashMap::new());
+        result_eq!("execute_channel_close_byte_code", result,
+                   ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Boolean(true), ByteCodeDataTypes::Integer64(1)]))
+    }
+    /*
+    Test every subscriber of a broadcast channel receives every value
+     */
+    fn execute_broadcast_byte_code() -> bool {
+        let (result, _) =
+            execute(vec![ByteCode::Broadcast(Some("events")),
+                         ByteCode::Subscribe(Some("events")), ByteCode::WriteVar("rx1"),
+                         ByteCode::Subscribe(Some("events")), ByteCode::WriteVar("rx2"),
+                         ByteCode::LoadVar(7), ByteCode::SendChannel(Some("events")), ByteCode::CloseChannel(Some("events")),
+                         ByteCode::ReceiveChannel(Some("rx1")), ByteCode::ReceiveChannel(Some("rx2")),
+                         ByteCode::IsClosed(Some("rx1")), ByteCode::List(3), ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    HashMap::new());
+        result_eq!("execute_broadcast_byte_code", result,
+                   ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Integer64(7), ByteCodeDataTypes::Integer64(7),
+                                                ByteCodeDataTypes::Boolean(true)]))
+    }
+    /*
+    Test value of a mutex is read and written inside WITH_LOCK and kept by the mutex
+     */
+    fn execute_mutex_byte_code() -> bool {
+        let increment = |var| vec![ByteCode::ReadVar(var), ByteCode::LoadVar(1), ByteCode::Add, ByteCode::WriteVar(var)];
+        let (result, _) =
+            execute(vec![ByteCode::LoadVar(1), ByteCode::Mutex("m"), ByteCode::ReadVar("m"), ByteCode::WriteVar("copy"),
+                         ByteCode::WithLock("m", increment("m")), ByteCode::WithLock("copy", increment("copy")),
+                         ByteCode::WithLock("m", vec![ByteCode::ReadVar("m"), ByteCode::WriteVar("value")]),
+                         ByteCode::ReadVar("value"), ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    HashMap::new());
+        result_eq!("execute_mutex_byte_code", result, ByteCodeDataTypes::Integer64(3))
+    }
+    /*
+    Test atomic cell is updated through a copy and compare and swap only stores the expected value
+     */
+    fn execute_atomic_byte_code() -> bool {
+        let (result, _) =
+            execute(vec![ByteCode::LoadVar(5), ByteCode::Atomic("a"), ByteCode::ReadVar("a"), ByteCode::WriteVar("copy"),
+                         ByteCode::LoadVar(2), ByteCode::AtomicAdd("copy"),
+                         ByteCode::LoadVar(5), ByteCode::LoadVar(0), ByteCode::CompareAndSwap("a"),
+                         ByteCode::LoadVar(7), ByteCode::LoadVar(1), ByteCode::CompareAndSwap("a"),
+                         ByteCode::AtomicLoad("copy"), ByteCode::List(4), ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    HashMap::new());
+        result_eq!("execute_atomic_byte_code", result,
+                   ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Integer64(5), ByteCodeDataTypes::Boolean(false),
+                                                ByteCodeDataTypes::Boolean(true), ByteCodeDataTypes::Integer64(1)]))
+    }
+    /*
+    Test wait group counted down by a function and barrier of one task passing at once
+     */
+    fn execute_wait_group_byte_code() -> bool {
+        let mut functions = HashMap::new();
+        functions.insert("finish", vec![ByteCode::WaitGroupDone("wg"), ByteCode::Return]);
+        let (result, _) =
+            execute(vec![ByteCode::WaitGroup("wg"), ByteCode::WaitGroupAdd(2, "wg"),
+                         ByteCode::FunctionCall("finish", vec![]), ByteCode::FunctionCall("finish", vec![]),
+                         ByteCode::WaitGroupWait("wg"), ByteCode::Barrier(1, "b"), ByteCode::BarrierWait("b"),
+                         ByteCode::BarrierWait("b"), ByteCode::LoadVar(1), ByteCode::ReturnValue],
+                    Vec::new(),
+                    HashMap::new(),
+                    functions);
+        result_eq!("execute_wait_group_byte_code", result, ByteCodeDataTypes::Integer64(1))
+    }
+    /*
     Read the byte codes from code folder and execute one by one
      */
     #[allow(dead_code)]
-    pub fn execute_byte_code_from_file(dir: &str, ext: &str) -> bool {
+    pub fn execute_byte_code_from_file(vm: &Vm, dir: &str, ext: &str) -> bool {
         let paths = fs::read_dir(dir).unwrap();
         let mut success = true;
         for path in paths {
@@ -428,7 +2023,7 @@ mod tests {
                 Ok(path) => {
                     let md = metadata(path.path()).unwrap();
                     if md.is_dir() {
-                        execute_byte_code_from_file(path.path().as_os_str().to_str().unwrap(), ext);
+                        execute_byte_code_from_file(vm, path.path().as_os_str().to_str().unwrap(), ext);
                     } else if md.is_file() {
                         let s = path.file_name();
                         let file_path = std::path::Path::new(s.as_os_str());
@@ -436,11 +2031,10 @@ mod tests {
                             let file_name = format!("{}", path.path().display());
                             //output_ln!(format!("Content Of File: {}", file_name));
                             let (byte_code, functions) = parse_code(path.path().to_str().unwrap());
-                            let (result, _) = execute(byte_code,
-                                                      Vec::new(),
-                                                      HashMap::new(),
-                                                      HashMap::new(),
-                                                      functions);
+                            let (result, _) = vm.execute(byte_code,
+                                                         Vec::new(),
+                                                         HashMap::new(),
+                                                         functions);
                             if !result!(format!("execute_byte_code_from_file({})", file_name), result) {
                                 success = false;
                             }
@@ -455,6 +2049,14 @@ mod tests {
     #[test]
     fn test_arithmetic() {
         assert_eq!(execute_arithmetic_byte_code(), true);
+        for (a, b, op) in [(i64::MAX, 1, ByteCode::Add), (i64::MIN, 1, ByteCode::Subtract),
+                           (i64::MAX, 2, ByteCode::Multiply), (i64::MIN, -1, ByteCode::Divide),
+                           (i64::MIN, -1, ByteCode::Modulo)] {
+            let (result, _) = execute(vec![ByteCode::LoadVar(7), ByteCode::LoadVar(a), ByteCode::LoadVar(b), op,
+                                           ByteCode::ReturnValue],
+                                      Vec::new(), HashMap::new(), HashMap::new());
+            assert!(matches!(result, Err(ByteCodeError::Overflow)));
+        }
     }
     #[test]
     fn test_compare() {
@@ -465,7 +2067,284 @@ mod tests {
         assert_eq!(execute_function_byte_code(), true);
     }
     #[test]
+    fn test_u256() {
+        assert!(execute_u256_byte_code());
+        let run = |code: Vec<ByteCode>| {
+            execute([code, vec![ByteCode::ReturnValue]].concat(), Vec::new(), HashMap::new(), HashMap::new()).0
+        };
+        assert!(matches!(run(vec![ByteCode::LoadU256(U256::ZERO.not()), ByteCode::ToI64]), Err(ByteCodeError::Overflow)));
+        assert!(matches!(run(vec![ByteCode::LoadVar(-1), ByteCode::ToU256]), Err(ByteCodeError::Overflow)));
+        assert!(matches!(run(vec![ByteCode::LoadVar(1), ByteCode::LoadVar(64), ByteCode::ShiftLeft]), Err(ByteCodeError::Overflow)));
+        assert!(matches!(run(vec![ByteCode::LoadU256(U256::from(1)), ByteCode::LoadVar(1), ByteCode::BitAnd]), Err(ByteCodeError::TypeMismatch)));
+        assert!(matches!(run(vec![ByteCode::LoadU256(U256::from(1)), ByteCode::LoadVar(1), ByteCode::LessThan]), Err(ByteCodeError::TypeMismatch)));
+    }
+    #[test]
+    fn test_decimal() {
+        assert!(execute_decimal_byte_code());
+    }
+    #[test]
+    fn test_function_value() {
+        assert!(execute_function_value_byte_code());
+    }
+    #[test]
+    fn test_list_errors() {
+        let run = |code: Vec<ByteCode>| {
+            execute([code, vec![ByteCode::ReturnValue]].concat(), Vec::new(), HashMap::new(), HashMap::new()).0
+        };
+        assert!(matches!(run(vec![ByteCode::LoadVar(1), ByteCode::LoadVar(0), ByteCode::ListGet]), Err(ByteCodeError::TypeMismatch)));
+        assert!(matches!(run(vec![ByteCode::List(0), ByteCode::LoadVar(0), ByteCode::ListGet]), Err(ByteCodeError::IndexOutOfBounds)));
+        assert!(matches!(run(vec![ByteCode::LoadVar(1), ByteCode::LoadVar(2), ByteCode::ListPush]), Err(ByteCodeError::TypeMismatch)));
+        assert!(matches!(run(vec![ByteCode::LoadVar(2), ByteCode::ListPush]), Err(ByteCodeError::StackUnderflow)));
+        assert!(matches!(run(vec![ByteCode::LoadVar(1), ByteCode::ListLen]), Err(ByteCodeError::TypeMismatch)));
+        assert!(matches!(run(vec![ByteCode::ListLen]), Err(ByteCodeError::StackUnderflow)));
+    }
+    #[test]
+    fn test_closure() {
+        assert!(execute_closure_byte_code());
+    }
+    #[test]
+    fn test_spawn_functions() {
+        assert!(execute_spawn_functions_byte_code());
+        let (result, _) = execute(vec![ByteCode::Spawn(vec![ByteCode::LoadVar(1), ByteCode::ReturnValue], vec!["missing"]),
+                                       ByteCode::Join(None), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::StackUnderflow)));
+    }
+    #[test]
+    fn test_channel_value() {
+        assert!(execute_channel_value_byte_code());
+    }
+    #[test]
+    fn test_channel_types() {
+        assert!(execute_channel_types_byte_code());
+    }
+    #[test]
+    fn test_bounded_channel() {
+        assert!(execute_bounded_channel_byte_code());
+    }
+    #[test]
+    fn test_fired_after_stays_open() {
+        let (result, _) =
+            execute(vec![ByteCode::After(5, Some("t")), ByteCode::ReceiveChannel(Some("t")),
+                         ByteCode::ReceiveTimeout(10, Some("t")), ByteCode::List(3), ByteCode::ReturnValue],
+                    Vec::new(), HashMap::new(), HashMap::new());
+        assert_eq!(result.unwrap(), ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Integer64(1), ByteCodeDataTypes::None,
+                                                                 ByteCodeDataTypes::Boolean(false)]));
+        let (result, _) =
+            execute(vec![ByteCode::After(5, Some("t")), ByteCode::ReceiveChannel(Some("t")),
+                         ByteCode::CloseChannel(Some("t")), ByteCode::ReceiveChannel(Some("t")), ByteCode::ReturnValue],
+                    Vec::new(), HashMap::new(), HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::ChannelClosed)));
+    }
+    #[test]
+    fn test_select() {
+        assert!(execute_select_byte_code());
+        let arm = |code| vec![ByteCode::LoadVar(1), ByteCode::WriteVar("kept"), ByteCode::Select(vec![(SelectCase::Default, code)]),
+                              ByteCode::ReadVar("kept"), ByteCode::ReturnValue];
+        let (result, _) = execute(arm(vec![ByteCode::ReadVar("missing")]), Vec::new(), HashMap::new(), HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::StackUnderflow)));
+        let (result, _) = execute(arm(vec![]), Vec::new(), HashMap::new(), HashMap::new());
+        assert_eq!(result.unwrap(), ByteCodeDataTypes::Integer64(1));
+        // a drained closed channel is skipped, once no arm is left SELECT fails
+        let select = || ByteCode::Select(vec![(SelectCase::Receive("rx"), vec![ByteCode::WriteVar("v")])]);
+        let code = |selects| [vec![ByteCode::Channel(None, Some(("tx", "rx"))), ByteCode::LoadVar(7), ByteCode::SendChannel(Some("tx")),
+                                   ByteCode::CloseChannel(Some("tx"))], vec![select(); selects], vec![ByteCode::ReadVar("v"), ByteCode::ReturnValue]].concat();
+        let (result, _) = execute(code(1), Vec::new(), HashMap::new(), HashMap::new());
+        assert_eq!(result.unwrap(), ByteCodeDataTypes::Integer64(7));
+        let (result, _) = execute(code(2), Vec::new(), HashMap::new(), HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::ChannelClosed)));
+    }
+    #[test]
+    fn test_receive_loop() {
+        let receive_loop = |body| vec![ByteCode::Channel(None, Some(("tx", "rx"))),
+                                       ByteCode::LoadVar(1), ByteCode::SendChannel(Some("tx")), ByteCode::LoadVar(2), ByteCode::SendChannel(Some("tx")),
+                                       ByteCode::CloseChannel(Some("tx")), ByteCode::ReceiveLoop("rx", body),
+                                       ByteCode::ReadVar("last"), ByteCode::ReturnValue];
+        let (result, _) = execute(receive_loop(vec![ByteCode::WriteVar("last")]), Vec::new(), HashMap::new(), HashMap::new());
+        assert_eq!(result.unwrap(), ByteCodeDataTypes::Integer64(2));
+        let (result, _) = execute(receive_loop(vec![ByteCode::WriteVar("last"), ByteCode::ReadVar("missing")]),
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::StackUnderflow)));
+    }
+    #[test]
+    fn test_channel_close() {
+        assert!(execute_channel_close_byte_code());
+        let (result, _) = execute(vec![ByteCode::Channel(None, Some(("tx", "rx"))), ByteCode::CloseChannel(Some("rx")),
+                                       ByteCode::LoadVar(1), ByteCode::SendChannel(Some("tx")), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::ChannelClosed)));
+    }
+    #[test]
+    fn test_broadcast() {
+        assert!(execute_broadcast_byte_code());
+    }
+    #[test]
+    fn test_mutex() {
+        assert!(execute_mutex_byte_code());
+        let mut vars = HashMap::new();
+        vars.insert("m", ByteCodeDataTypes::Mutex(SharedMutex::new(ByteCodeDataTypes::Integer64(1))));
+        let (result, _) = execute(vec![ByteCode::WithLock("m", vec![ByteCode::ReadVar("missing")])],
+                                  Vec::new(),
+                                  vars.clone(),
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::StackUnderflow)));
+        let (result, _) = execute(vec![ByteCode::WithLock("m", vec![])],
+                                  Vec::new(),
+                                  vars,
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::LockPoisoned)));
+        // a body ending without value unlocks, RETURN_VALUE on an empty stack is an error
+        let fresh = || HashMap::from([("m", ByteCodeDataTypes::Mutex(SharedMutex::new(ByteCodeDataTypes::Integer64(1))))]);
+        let (result, _) = execute(vec![ByteCode::WithLock("m", vec![]), ByteCode::WithLock("m", vec![]), ByteCode::LoadVar(5), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  fresh(),
+                                  HashMap::new());
+        assert_eq!(result.unwrap(), ByteCodeDataTypes::Integer64(5));
+        let (result, _) = execute(vec![ByteCode::WithLock("m", vec![ByteCode::ReturnValue]), ByteCode::LoadVar(5), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  fresh(),
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::UnknownByteCode)));
+    }
+    #[test]
+    fn test_atomic() {
+        assert!(execute_atomic_byte_code());
+    }
+    #[test]
+    fn test_wait_group() {
+        assert!(execute_wait_group_byte_code());
+        let (result, _) = execute(vec![ByteCode::WaitGroup("wg"), ByteCode::WaitGroupDone("wg"), ByteCode::LoadVar(1), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::NegativeWaitGroup)));
+    }
+    #[test]
+    fn test_supervise() {
+        // the supervised task panics, ignoring it finishes the task with None after reporting the panic
+        let (result, _) = execute(vec![ByteCode::Channel(None, Some(("reports", "failures"))),
+                                       ByteCode::Supervise(Supervision::Ignore, Some("reports"), vec![ByteCode::PrintLn("missing")]),
+                                       ByteCode::WriteVar("task"), ByteCode::Join(Some("task")),
+                                       ByteCode::ReceiveChannel(Some("failures")), ByteCode::List(2), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        match result.unwrap() {
+            ByteCodeDataTypes::List(values) => {
+                assert_eq!(values[0], ByteCodeDataTypes::None);
+                assert!(matches!(&values[1], ByteCodeDataTypes::List(report)
+                    if matches!(report[1], ByteCodeDataTypes::Error(ByteCodeError::Panicked(_)))));
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+        let (result, _) = execute(vec![ByteCode::Supervise(Supervision::Escalate, None, vec![ByteCode::LoadVar(1), ByteCode::CallIndirect(vec![])]),
+                                       ByteCode::Join(None), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::NotAFunction)), "{:?}", result);
+        let (result, _) = execute(vec![ByteCode::Spawn(vec![ByteCode::PrintLn("missing")], vec![]),
+                                       ByteCode::Join(None), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::Panicked(_))), "{:?}", result);
+        // every failure is reported even though the channel holds only one report at a time
+        let (result, _) = execute(vec![ByteCode::Channel(Some(1), Some(("reports", "failures"))),
+                                       ByteCode::Supervise(Supervision::Restart(2), Some("reports"), vec![ByteCode::PrintLn("missing")]),
+                                       ByteCode::WriteVar("task"),
+                                       ByteCode::ReceiveChannel(Some("failures")), ByteCode::ReceiveChannel(Some("failures")),
+                                       ByteCode::ReceiveChannel(Some("failures")), ByteCode::List(3), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        assert!(matches!(&result, Ok(ByteCodeDataTypes::List(reports)) if reports.len() == 3), "{:?}", result);
+    }
+    #[test]
+    fn test_parallel_map_and_reduce() {
+        let functions = HashMap::from([
+            ("square", vec![ByteCode::WriteVar("x"), ByteCode::ReadVar("x"), ByteCode::ReadVar("x"), ByteCode::Multiply, ByteCode::ReturnValue]),
+            ("add", vec![ByteCode::Add, ByteCode::ReturnValue]),
+            ("apply", vec![ByteCode::CallIndirect(vec![]), ByteCode::ReturnValue]),
+            ("slow_fail", vec![ByteCode::SleepMs(50), ByteCode::LoadVar(1), ByteCode::CallIndirect(vec![]), ByteCode::ReturnValue]),
+            ("fast_fail", vec![ByteCode::Add, ByteCode::ReturnValue]),
+            ("seven", vec![ByteCode::LoadVar(7), ByteCode::ReturnValue]),
+        ]);
+        let numbers = |n: i64| (1..=n).map(ByteCode::LoadVar).chain([ByteCode::List(n as usize)]).collect::<Vec<_>>();
+        for vm in [Vm::new(), Vm::green(2), Vm::seeded(3), Vm::pooled(2)] {
+            let run = |code: Vec<ByteCode>| vm.execute(code, Vec::new(), HashMap::new(), functions.clone()).0;
+            let mut code = numbers(5);
+            code.extend([ByteCode::ParMap(Some("square")), ByteCode::WriteVar("squares"), ByteCode::ReadVar("squares"),
+                         ByteCode::LoadFunction("add"), ByteCode::ParReduce(None), ByteCode::ReadVar("squares"), ByteCode::List(2),
+                         ByteCode::ReturnValue]);
+            let squares = ByteCodeDataTypes::List([1, 4, 9, 16, 25].map(ByteCodeDataTypes::Integer64).to_vec());
+            assert_eq!(run(code).unwrap(), ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Integer64(55), squares]));
+            for (n, reduced) in [(0, ByteCodeDataTypes::None), (1, ByteCodeDataTypes::Integer64(1))] {
+                let mut code = numbers(n);
+                code.extend([ByteCode::ParReduce(Some("add")), ByteCode::ReturnValue]);
+                assert_eq!(run(code).unwrap(), reduced);
+            }
+            // the error of the first element is returned even though the last element fails first
+            let result = run(vec![ByteCode::LoadFunction("slow_fail"), ByteCode::LoadFunction("seven"), ByteCode::LoadFunction("fast_fail"),
+                                  ByteCode::List(3), ByteCode::ParMap(Some("apply")), ByteCode::ReturnValue]);
+            assert!(matches!(result, Err(ByteCodeError::NotAFunction)), "{:?}", result);
+        }
+    }
+    #[test]
+    fn test_mailbox() {
+        let (result, _) = execute(vec![ByteCode::LoadVar(5), ByteCode::SelfTask, ByteCode::SendTo(None),
+                                       ByteCode::LoadVar(6), ByteCode::SelfTask, ByteCode::SendTo(None),
+                                       ByteCode::Receive, ByteCode::Receive, ByteCode::List(2), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        assert_eq!(result.unwrap(), ByteCodeDataTypes::List(vec![ByteCodeDataTypes::Integer64(5), ByteCodeDataTypes::Integer64(6)]));
+        let (result, _) = execute(vec![ByteCode::Receive, ByteCode::ReturnValue], Vec::new(), HashMap::new(), HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::Deadlock(tasks)) if tasks[0].opcode == "RECEIVE"));
+    }
+    #[test]
+    fn test_send_to_finished_task() {
+        let (result, _) = execute(vec![ByteCode::Spawn(vec![ByteCode::LoadVar(1), ByteCode::ReturnValue], vec![]),
+                                       ByteCode::WriteVar("task"), ByteCode::Join(Some("task")),
+                                       ByteCode::LoadVar(2), ByteCode::SendTo(Some("task")), ByteCode::LoadVar(3), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::ChannelClosed)), "{:?}", result);
+    }
+    #[test]
+    fn test_call_non_function() {
+        let (result, _) = execute(vec![ByteCode::LoadVar(1), ByteCode::CallIndirect(vec![]), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::NotAFunction)));
+        let (result, _) = execute(vec![ByteCode::CallIndirect(vec![]), ByteCode::ReturnValue],
+                                  Vec::new(),
+                                  HashMap::new(),
+                                  HashMap::new());
+        assert!(matches!(result, Err(ByteCodeError::StackUnderflow)));
+    }
+    #[test]
     fn test_execute_files() {
-        assert_eq!(execute_byte_code_from_file("./code", "bc"), true);
+        assert!(execute_byte_code_from_file(&Vm::new().with_virtual_clock(), "./code", "bc"));
+    }
+    #[test]
+    fn test_execute_files_green() {
+        assert!(execute_byte_code_from_file(&Vm::green(2).with_virtual_clock(), "./code", "bc"));
+    }
+    #[test]
+    fn test_execute_files_seeded() {
+        assert!(execute_byte_code_from_file(&Vm::seeded(42).with_virtual_clock(), "./code", "bc"));
+    }
+    #[test]
+    fn test_execute_files_pooled() {
+        assert!(execute_byte_code_from_file(&Vm::pooled(4).with_virtual_clock(), "./code", "bc"));
     }
 }
@@ -1,3 +1,15 @@
+mod atomic;
+mod barrier;
+mod channel;
+mod clock;
+mod decimal;
 mod interpreter;
 mod line_count;
-mod parse_code;
\ No newline at end of file
+mod mutex;
+mod parse_code;
+mod pool;
+mod runtime;
+mod task;
+mod u256;
+mod wait_group;
+mod waker;
\ No newline at end of file
@@ -0,0 +1,146 @@
+pub mod mutex {
+    use crate::interpreter::interpreter::{ByteCodeDataTypes, ByteCodeError};
+    use crate::waker::waker::{WaitQueue, Waker};
+    use std::fmt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    /*
+    Counter to give every mutex a unique id
+     */
+    static NEXT_MUTEX_ID: AtomicU64 = AtomicU64::new(1);
+    /*
+    Protected value, taken out while the mutex is locked and put back on unlock
+     */
+    struct MutexState {
+        value: Option<ByteCodeDataTypes>,
+        poisoned: bool,
+        waiters: WaitQueue,
+    }
+    struct MutexCell {
+        id: u64,
+        state: Mutex<MutexState>,
+    }
+    /*
+    Mutex shared by every task holding a copy of it, created by MUTEX and used by WITH_LOCK
+     */
+    #[derive(Clone)]
+    pub struct SharedMutex {
+        cell: Arc<MutexCell>,
+    }
+    /*
+    Lock held by a task, dropping it without unlock (e.g. on panic) poisons the mutex
+     */
+    pub struct LockGuard {
+        mutex: SharedMutex,
+        released: bool,
+    }
+
+    impl SharedMutex {
+        pub fn new(value: ByteCodeDataTypes) -> SharedMutex {
+            SharedMutex {
+                cell: Arc::new(MutexCell {
+                    id: NEXT_MUTEX_ID.fetch_add(1, Ordering::Relaxed),
+                    state: Mutex::new(MutexState { value: Some(value), poisoned: false, waiters: WaitQueue::default() }),
+                }),
+            }
+        }
+        /*
+        Take the value and lock if the mutex is free, otherwise register waker (when given) and return None
+         */
+        pub fn poll_lock(&self, waker: Option<&Waker>) -> Option<Result<(LockGuard, ByteCodeDataTypes), ByteCodeError>> {
+            let mut state = self.cell.state.lock().unwrap();
+            if state.poisoned {
+                return Some(Err(ByteCodeError::LockPoisoned));
+            }
+            match state.value.take() {
+                Some(value) => Some(Ok((LockGuard { mutex: self.clone(), released: false }, value))),
+                None => {
+                    if let Some(waker) = waker {
+                        state.waiters.register(waker);
+                    }
+                    None
+                },
+            }
+        }
+        fn release(&self, value: Option<ByteCodeDataTypes>) {
+            let mut state = self.cell.state.lock().unwrap();
+            match value {
+                Some(value) => state.value = Some(value),
+                None => state.poisoned = true,
+            }
+            state.waiters.wake_all();
+        }
+    }
+
+    impl LockGuard {
+        /*
+        Store new value and let the next task lock the mutex
+         */
+        pub fn unlock(mut self, value: ByteCodeDataTypes) {
+            self.released = true;
+            self.mutex.release(Some(value));
+        }
+        /*
+        Release the mutex without a value, every later lock fails with LockPoisoned
+         */
+        pub fn poison(mut self) {
+            self.released = true;
+            self.mutex.release(None);
+        }
+    }
+    impl Drop for LockGuard {
+        fn drop(&mut self) {
+            if !self.released {
+                self.mutex.release(None);
+            }
+        }
+    }
+    impl PartialEq for SharedMutex {
+        fn eq(&self, other: &SharedMutex) -> bool {
+            Arc::ptr_eq(&self.cell, &other.cell)
+        }
+    }
+    impl Eq for SharedMutex {}
+    impl fmt::Debug for SharedMutex {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Mutex({})", self.cell.id)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::interpreter::{ByteCodeDataTypes, ByteCodeError};
+    use crate::mutex::mutex::SharedMutex;
+    use crate::waker::waker::block_on;
+    use std::thread;
+    #[test]
+    fn lock_gives_exclusive_access_to_value() {
+        let mutex = SharedMutex::new(ByteCodeDataTypes::Integer64(0));
+        let workers: Vec<_> = (0..4).map(|_| {
+            let mutex = mutex.clone();
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    let (guard, value) = block_on(|waker| mutex.poll_lock(Some(waker))).unwrap();
+                    match value {
+                        ByteCodeDataTypes::Integer64(v) => guard.unlock(ByteCodeDataTypes::Integer64(v + 1)),
+                        _ => guard.poison(),
+                    }
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(block_on(|waker| mutex.poll_lock(Some(waker))).unwrap().1, ByteCodeDataTypes::Integer64(400));
+    }
+    #[test]
+    fn panic_while_locked_poisons_mutex() {
+        let mutex = SharedMutex::new(ByteCodeDataTypes::None);
+        let locked = mutex.clone();
+        assert!(thread::spawn(move || {
+            let _lock = block_on(|waker| locked.poll_lock(Some(waker))).unwrap();
+            panic!("failed while locked");
+        }).join().is_err());
+        assert!(matches!(block_on(|waker| mutex.poll_lock(Some(waker))), Err(ByteCodeError::LockPoisoned)));
+    }
+}
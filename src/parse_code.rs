@@ -1,5 +1,7 @@
 pub mod parse_code {
-    use crate::interpreter::interpreter::ByteCode;
+    use crate::interpreter::interpreter::{ByteCode, SelectCase, Supervision};
+    use crate::u256::u256::U256;
+    use crate::decimal::decimal::{Decimal, RoundingMode};
     use std::collections::HashMap;
     use std::fs;
     /*
@@ -13,6 +15,10 @@ pub mod parse_code {
         LoopCode,
         Function,
         Spawn,
+        Supervise,
+        WithLock,
+        ReceiveLoop,
+        Select,
     }
     /*
     Convert a token to static native string
@@ -54,24 +60,78 @@ pub mod parse_code {
         let mut function_name: &'static str = "";
         let mut spawn_code = vec![];
         let mut spawn_vars = vec![];
+        // blocks that can be nested in a block of the same kind keep their buffers on a stack, innermost last
+        let mut supervise_blocks: Vec<(Option<_>, Vec<ByteCode>)> = vec![];
+        let mut lock_blocks: Vec<(&'static str, Vec<ByteCode>)> = vec![];
+        let mut receive_loop_blocks: Vec<(&'static str, Vec<ByteCode>)> = vec![];
+        let mut select_blocks: Vec<(Vec<_>, Option<_>, Vec<ByteCode>)> = vec![];
         let mut tmp_code = vec![];
         let mut code_type = CodeType::Program;
         let mut code_type_stack: Vec<CodeType> = vec![];
         let lines: Vec<String> = data.lines().map(String::from).collect();
         let mut token: Vec<&str>;
         let mut code_split;
+        /*
+        Move pending instructions to the code block that is currently being parsed
+         */
+        macro_rules! flush_tmp_code {
+            () => {
+                match code_type {
+                    CodeType::Program => byte_code.append(&mut tmp_code.to_vec()),
+                    CodeType::LoopCondition => loop_condition.append(&mut tmp_code.to_vec()),
+                    CodeType::LoopCode => loop_code.append(&mut tmp_code.to_vec()),
+                    CodeType::Function => function_code.append(&mut tmp_code.to_vec()),
+                    CodeType::Spawn => spawn_code.append(&mut tmp_code.to_vec()),
+                    CodeType::Supervise => supervise_blocks.last_mut().unwrap().1.append(&mut tmp_code.to_vec()),
+                    CodeType::WithLock => lock_blocks.last_mut().unwrap().1.append(&mut tmp_code.to_vec()),
+                    CodeType::ReceiveLoop => receive_loop_blocks.last_mut().unwrap().1.append(&mut tmp_code.to_vec()),
+                    CodeType::Select => select_blocks.last_mut().unwrap().2.append(&mut tmp_code.to_vec()),
+                }
+            };
+        }
+        /*
+        Start next arm of the SELECT block being parsed, finishing the previous arm
+         */
+        macro_rules! select_case {
+            ($case:expr) => {{
+                let (select_arms, select_case, select_code) = select_blocks.last_mut().unwrap();
+                select_code.append(&mut tmp_code.to_vec());
+                tmp_code.clear();
+                if let Some(case) = select_case.replace($case) {
+                    select_arms.push((case, select_code.to_vec()));
+                    select_code.clear();
+                }
+            }};
+        }
         for code in lines {
             //println!("{}", code);
             code_split = Box::leak(code.into_boxed_str());
             token = code_split.split_whitespace().collect();
             match token[0] {
                 "LOAD_VAL" => tmp_code.push(ByteCode::LoadVar(token[1].parse::<i64>().unwrap())),
+                "LOAD_U256" => tmp_code.push(ByteCode::LoadU256(token[1].parse::<U256>().unwrap())),
+                "LOAD_DEC" => tmp_code.push(ByteCode::LoadDecimal(token[1].parse::<Decimal>().unwrap())),
                 "WRITE_VAR" => tmp_code.push(ByteCode::WriteVar(get_var!(token[1]))),
                 "READ_VAR" => tmp_code.push(ByteCode::ReadVar(get_var!(token[1]))),
                 "ADD" => tmp_code.push(ByteCode::Add),
                 "SUBTRACT" => tmp_code.push(ByteCode::Subtract),
                 "MULTIPLY" => tmp_code.push(ByteCode::Multiply),
                 "DIVIDE" => tmp_code.push(ByteCode::Divide),
+                "MODULO" => tmp_code.push(ByteCode::Modulo),
+                "BIT_AND" => tmp_code.push(ByteCode::BitAnd),
+                "BIT_OR" => tmp_code.push(ByteCode::BitOr),
+                "BIT_XOR" => tmp_code.push(ByteCode::BitXor),
+                "BIT_NOT" => tmp_code.push(ByteCode::BitNot),
+                "SHIFT_LEFT" => tmp_code.push(ByteCode::ShiftLeft),
+                "SHIFT_RIGHT" => tmp_code.push(ByteCode::ShiftRight),
+                "TO_U256" => tmp_code.push(ByteCode::ToU256),
+                "TO_I64" => tmp_code.push(ByteCode::ToI64),
+                "MUL_DEC" => tmp_code.push(ByteCode::MulDecimal(token[1].parse::<u32>().unwrap(),
+                                                                token[2].parse::<RoundingMode>().unwrap())),
+                "DIV_DEC" => tmp_code.push(ByteCode::DivDecimal(token[1].parse::<u32>().unwrap(),
+                                                                token[2].parse::<RoundingMode>().unwrap())),
+                "ROUND_DEC" => tmp_code.push(ByteCode::RoundDecimal(token[1].parse::<u32>().unwrap(),
+                                                                    token[2].parse::<RoundingMode>().unwrap())),
                 "LESS_THAN" => tmp_code.push(ByteCode::LessThan),
                 "LESS_THAN_EQUAL" => tmp_code.push(ByteCode::LessThanEqual),
                 "GREATER_THAN" => tmp_code.push(ByteCode::GreaterThan),
@@ -81,15 +141,14 @@ pub mod parse_code {
                 "PRINT" => tmp_code.push(ByteCode::Print(get_var!(token[1]))),
                 "PRINT_LN" => tmp_code.push(ByteCode::PrintLn(get_var!(token[1]))),
                 "SLEEP" => tmp_code.push(ByteCode::Sleep(token[1].parse::<u64>().unwrap())),
+                "SLEEP_MS" => tmp_code.push(ByteCode::SleepMs(token[1].parse::<u64>().unwrap())),
+                "AFTER" => tmp_code.push(ByteCode::After(token[1].parse::<u64>().unwrap(), token.get(2).map(|var| get_var!(*var)))),
+                "TICKER" => tmp_code.push(ByteCode::Ticker(token[1].parse::<u64>().unwrap(), token.get(2).map(|var| get_var!(*var)))),
+                "NOW" => tmp_code.push(ByteCode::Now),
+                "YIELD" => tmp_code.push(ByteCode::Yield),
                 "LOOP" => {
                     code_type_stack.push(code_type.clone());
-                    match code_type {
-                        CodeType::Program => byte_code.append(&mut tmp_code.to_vec()),
-                        CodeType::LoopCondition => loop_condition.append(&mut tmp_code.to_vec()),
-                        CodeType::LoopCode => loop_code.append(&mut tmp_code.to_vec()),
-                        CodeType::Function => function_code.append(&mut tmp_code.to_vec()),
-                        CodeType::Spawn => spawn_code.append(&mut tmp_code.to_vec()),
-                    }
+                    flush_tmp_code!();
                     tmp_code.clear();
                     code_type = CodeType::LoopCondition;
                 }
@@ -124,10 +183,17 @@ pub mod parse_code {
                 }
                 "FUNC_CALL" =>
                     tmp_code.push(ByteCode::FunctionCall(get_var!(token[1]), copy_vars!(token, 2))),
+                "LOAD_FUNC" => tmp_code.push(ByteCode::LoadFunction(get_var!(token[1]))),
+                "CLOSURE" => tmp_code.push(ByteCode::Closure(get_var!(token[1]), copy_vars!(token, 2))),
+                "CALL_INDIRECT" => tmp_code.push(ByteCode::CallIndirect(copy_vars!(token, 1))),
+                "LIST" => tmp_code.push(ByteCode::List(token[1].parse::<usize>().unwrap())),
+                "LIST_GET" => tmp_code.push(ByteCode::ListGet),
+                "LIST_PUSH" => tmp_code.push(ByteCode::ListPush),
+                "LIST_LEN" => tmp_code.push(ByteCode::ListLen),
                 "SPAWN" => {
                     code_type_stack.push(code_type.clone());
+                    flush_tmp_code!();
                     code_type = CodeType::Spawn;
-                    byte_code.append(&mut tmp_code.to_vec());
                     tmp_code.clear();
                     spawn_code.clear();
                     spawn_vars.append(&mut copy_vars!(token, 1));
@@ -140,9 +206,125 @@ pub mod parse_code {
                     spawn_code.clear();
                     spawn_vars.clear();
                 },
-                "CHANNEL" => tmp_code.push(ByteCode::Channel(get_var!(token[1]), get_var!(token[2]))),
-                "SEND_CHANNEL" => tmp_code.push(ByteCode::SendChannel(get_var!(token[1]))),
-                "RECEIVE_CHANNEL" => tmp_code.push(ByteCode::ReceiveChannel(get_var!(token[1]))),
+                "SUPERVISE" => {
+                    code_type_stack.push(code_type.clone());
+                    flush_tmp_code!();
+                    code_type = CodeType::Supervise;
+                    tmp_code.clear();
+                    // an unknown policy turns the block into an instruction that fails when it is executed
+                    let supervision = match token[1] {
+                        "RESTART" => Some((Supervision::Restart(token[2].parse::<usize>().unwrap()), token.get(3))),
+                        "IGNORE" => Some((Supervision::Ignore, token.get(2))),
+                        "ESCALATE" => Some((Supervision::Escalate, token.get(2))),
+                        _ => None,
+                    }.map(|(policy, creports)| (policy, creports.map(|var| get_var!(*var))));
+                    supervise_blocks.push((supervision, vec![]));
+                },
+                "SUPERVISE_END" => {
+                    code_type = code_type_stack.pop().unwrap();
+                    let (supervision, mut supervised_code) = supervise_blocks.pop().unwrap();
+                    supervised_code.append(&mut tmp_code.to_vec());
+                    tmp_code.clear();
+                    match supervision {
+                        Some((policy, creports)) => tmp_code.push(ByteCode::Supervise(policy, creports, supervised_code)),
+                        None => tmp_code.push(ByteCode::Unknown),
+                    }
+                },
+                "SPAWN_FN" => tmp_code.push(ByteCode::SpawnFunctions(token[1].parse::<usize>().unwrap(),
+                                                                     token.get(2).map_or(0, |argc| argc.parse::<usize>().unwrap()))),
+                "JOIN" | "AWAIT" => tmp_code.push(ByteCode::Join(token.get(1).map(|var| get_var!(*var)))),
+                "JOIN_ALL" => tmp_code.push(ByteCode::JoinAll),
+                "PAR_MAP" => tmp_code.push(ByteCode::ParMap(token.get(1).map(|func| get_var!(*func)))),
+                "PAR_REDUCE" => tmp_code.push(ByteCode::ParReduce(token.get(1).map(|func| get_var!(*func)))),
+                "SELF" => tmp_code.push(ByteCode::SelfTask),
+                "SEND_TO" => tmp_code.push(ByteCode::SendTo(token.get(1).map(|var| get_var!(*var)))),
+                "RECEIVE" => tmp_code.push(ByteCode::Receive),
+                "CANCEL" => tmp_code.push(ByteCode::Cancel(token.get(1).map(|var| get_var!(*var)))),
+                "CHANNEL" => match token.len() {
+                    1 => tmp_code.push(ByteCode::Channel(None, None)),
+                    3 => tmp_code.push(ByteCode::Channel(None, Some((get_var!(token[1]), get_var!(token[2]))))),
+                    _ => tmp_code.push(ByteCode::Unknown),
+                },
+                "CHANNEL_BOUNDED" => {
+                    let capacity = token[1].parse::<usize>().unwrap();
+                    match token.len() {
+                        2 => tmp_code.push(ByteCode::Channel(Some(capacity), None)),
+                        4 => tmp_code.push(ByteCode::Channel(Some(capacity), Some((get_var!(token[2]), get_var!(token[3]))))),
+                        _ => tmp_code.push(ByteCode::Unknown),
+                    }
+                },
+                "SEND_CHANNEL" => tmp_code.push(ByteCode::SendChannel(token.get(1).map(|var| get_var!(*var)))),
+                "RECEIVE_CHANNEL" => tmp_code.push(ByteCode::ReceiveChannel(token.get(1).map(|var| get_var!(*var)))),
+                "TRY_SEND" => tmp_code.push(ByteCode::TrySend(token.get(1).map(|var| get_var!(*var)))),
+                "TRY_RECEIVE" => tmp_code.push(ByteCode::TryReceive(token.get(1).map(|var| get_var!(*var)))),
+                "RECEIVE_TIMEOUT" => tmp_code.push(ByteCode::ReceiveTimeout(token[1].parse::<u64>().unwrap(),
+                                                                            token.get(2).map(|var| get_var!(*var)))),
+                "MUTEX" => tmp_code.push(ByteCode::Mutex(get_var!(token[1]))),
+                "WITH_LOCK" => {
+                    code_type_stack.push(code_type.clone());
+                    flush_tmp_code!();
+                    code_type = CodeType::WithLock;
+                    tmp_code.clear();
+                    lock_blocks.push((get_var!(token[1]), vec![]));
+                },
+                "END_LOCK" => {
+                    code_type = code_type_stack.pop().unwrap();
+                    let (lock_var, mut lock_code) = lock_blocks.pop().unwrap();
+                    lock_code.append(&mut tmp_code.to_vec());
+                    tmp_code.clear();
+                    tmp_code.push(ByteCode::WithLock(lock_var, lock_code));
+                },
+                "ATOMIC" => tmp_code.push(ByteCode::Atomic(get_var!(token[1]))),
+                "ATOMIC_ADD" => tmp_code.push(ByteCode::AtomicAdd(get_var!(token[1]))),
+                "ATOMIC_LOAD" => tmp_code.push(ByteCode::AtomicLoad(get_var!(token[1]))),
+                "ATOMIC_STORE" => tmp_code.push(ByteCode::AtomicStore(get_var!(token[1]))),
+                "COMPARE_AND_SWAP" => tmp_code.push(ByteCode::CompareAndSwap(get_var!(token[1]))),
+                "WAITGROUP" => tmp_code.push(ByteCode::WaitGroup(get_var!(token[1]))),
+                "WG_ADD" => tmp_code.push(ByteCode::WaitGroupAdd(token[1].parse::<i64>().unwrap(), get_var!(token[2]))),
+                "WG_DONE" => tmp_code.push(ByteCode::WaitGroupDone(get_var!(token[1]))),
+                "WG_WAIT" => tmp_code.push(ByteCode::WaitGroupWait(get_var!(token[1]))),
+                "BARRIER" => tmp_code.push(ByteCode::Barrier(token[1].parse::<usize>().unwrap(), get_var!(token[2]))),
+                "BARRIER_WAIT" => tmp_code.push(ByteCode::BarrierWait(get_var!(token[1]))),
+                "CLONE_SENDER" => tmp_code.push(ByteCode::CloneSender(token.get(1).map(|var| get_var!(*var)))),
+                "BROADCAST" => tmp_code.push(ByteCode::Broadcast(token.get(1).map(|var| get_var!(*var)))),
+                "SUBSCRIBE" => tmp_code.push(ByteCode::Subscribe(token.get(1).map(|var| get_var!(*var)))),
+                "CLOSE_CHANNEL" => tmp_code.push(ByteCode::CloseChannel(token.get(1).map(|var| get_var!(*var)))),
+                "IS_CLOSED" => tmp_code.push(ByteCode::IsClosed(token.get(1).map(|var| get_var!(*var)))),
+                "RECEIVE_LOOP" => {
+                    code_type_stack.push(code_type.clone());
+                    flush_tmp_code!();
+                    code_type = CodeType::ReceiveLoop;
+                    tmp_code.clear();
+                    receive_loop_blocks.push((get_var!(token[1]), vec![]));
+                },
+                "RECEIVE_LOOP_END" => {
+                    code_type = code_type_stack.pop().unwrap();
+                    let (receive_loop_var, mut receive_loop_code) = receive_loop_blocks.pop().unwrap();
+                    receive_loop_code.append(&mut tmp_code.to_vec());
+                    tmp_code.clear();
+                    tmp_code.push(ByteCode::ReceiveLoop(receive_loop_var, receive_loop_code));
+                },
+                "SELECT" => {
+                    code_type_stack.push(code_type.clone());
+                    flush_tmp_code!();
+                    code_type = CodeType::Select;
+                    tmp_code.clear();
+                    select_blocks.push((vec![], None, vec![]));
+                },
+                "CASE_RECEIVE" => select_case!(SelectCase::Receive(get_var!(token[1]))),
+                "CASE_SEND" => select_case!(SelectCase::Send(get_var!(token[1]), get_var!(token[2]))),
+                "CASE_TIMEOUT" => select_case!(SelectCase::Timeout(token[1].parse::<u64>().unwrap())),
+                "CASE_DEFAULT" => select_case!(SelectCase::Default),
+                "SELECT_END" => {
+                    code_type = code_type_stack.pop().unwrap();
+                    let (mut select_arms, select_case, mut select_code) = select_blocks.pop().unwrap();
+                    select_code.append(&mut tmp_code.to_vec());
+                    tmp_code.clear();
+                    if let Some(case) = select_case {
+                        select_arms.push((case, select_code));
+                    }
+                    tmp_code.push(ByteCode::Select(select_arms));
+                },
                 _ => {}
             }
         }
@@ -150,4 +332,49 @@ pub mod parse_code {
         tmp_code.clear();
         (byte_code, functions)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::interpreter::{ByteCode, SelectCase, Supervision};
+    use crate::parse_code::parse_code::parse_code;
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+    /*
+    Write source to a file in the temp folder and parse it
+     */
+    fn parse_source(name: &str, source: &str) -> (Vec<ByteCode>, HashMap<&'static str, Vec<ByteCode>>) {
+        let path = env::temp_dir().join(name);
+        fs::write(&path, source).unwrap();
+        let parsed = parse_code(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        parsed
+    }
+    #[test]
+    fn channel_takes_no_or_two_end_point_names() {
+        let (code, _) = parse_source("composable_channel.bc", "CHANNEL\nCHANNEL tx rx\nCHANNEL tx\nCHANNEL tx rx extra");
+        assert_eq!(code, vec![ByteCode::Channel(None, None), ByteCode::Channel(None, Some(("tx", "rx"))),
+                              ByteCode::Unknown, ByteCode::Unknown]);
+        let (code, _) = parse_source("composable_channel_bounded.bc", "CHANNEL_BOUNDED 1\nCHANNEL_BOUNDED 0 tx rx\nCHANNEL_BOUNDED 1 tx");
+        assert_eq!(code, vec![ByteCode::Channel(Some(1), None), ByteCode::Channel(Some(0), Some(("tx", "rx"))), ByteCode::Unknown]);
+    }
+    #[test]
+    fn unknown_supervision_policy_is_parsed_as_unknown_instruction() {
+        let (code, _) = parse_source("composable_supervise.bc",
+                                     "SUPERVISE SOMETIMES\nLOAD_VAL 1\nRETURN_VALUE\nSUPERVISE_END\n\
+                                      SUPERVISE IGNORE\nRETURN\nSUPERVISE_END\nJOIN");
+        assert_eq!(code, vec![ByteCode::Unknown, ByteCode::Supervise(Supervision::Ignore, None, vec![ByteCode::Return]),
+                              ByteCode::Join(None)]);
+    }
+    #[test]
+    fn nested_select_keeps_arms_of_outer_select() {
+        let (code, _) = parse_source("composable_nested_select.bc",
+                                     "SELECT\nCASE_RECEIVE a\nPRINT_LN a\n\
+                                      SELECT\nCASE_RECEIVE b\nPRINT_LN b\nCASE_DEFAULT\nRETURN\nSELECT_END\n\
+                                      PRINT_LN a\nCASE_TIMEOUT 5\nRETURN\nSELECT_END");
+        let inner = ByteCode::Select(vec![(SelectCase::Receive("b"), vec![ByteCode::PrintLn("b")]),
+                                          (SelectCase::Default, vec![ByteCode::Return])]);
+        assert_eq!(code, vec![ByteCode::Select(vec![(SelectCase::Receive("a"), vec![ByteCode::PrintLn("a"), inner, ByteCode::PrintLn("a")]),
+                                                    (SelectCase::Timeout(5), vec![ByteCode::Return])])]);
+    }
+}
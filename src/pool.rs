@@ -0,0 +1,153 @@
+pub mod pool {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::thread;
+    type Job = Box<dyn FnOnce() + Send>;
+    /*
+    Tasks waiting for a worker with their task id, the number of worker threads and how many of them
+    wait for a job, and the number of tasks running and completed
+     */
+    struct PoolState {
+        jobs: VecDeque<(u64, Job)>,
+        workers: usize,
+        idle: usize,
+        running: usize,
+        completed: u64,
+        shutdown: bool,
+    }
+    /*
+    Fixed number of OS threads running spawned tasks, tasks spawned while every worker is busy are queued.
+    Workers are started when they are first needed
+     */
+    pub struct Pool {
+        size: usize,
+        state: Mutex<PoolState>,
+        work: Condvar,
+        done: Condvar,
+    }
+    /*
+    Snapshot of the pool - its size, tasks waiting for a worker, tasks running on a worker (waiting tasks
+    included, they keep their worker) and tasks that finished
+     */
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct PoolMetrics {
+        pub size: usize,
+        pub queued: usize,
+        pub running: usize,
+        pub completed: u64,
+    }
+
+    impl Pool {
+        pub fn new(size: usize) -> Arc<Pool> {
+            Arc::new(Pool {
+                size: size.max(1),
+                state: Mutex::new(PoolState { jobs: VecDeque::new(), workers: 0, idle: 0, running: 0, completed: 0, shutdown: false }),
+                work: Condvar::new(),
+                done: Condvar::new(),
+            })
+        }
+        /*
+        Run job of task on a worker, queue it if every worker is busy
+         */
+        pub fn submit(self: &Arc<Pool>, task: u64, job: Job) {
+            let mut state = self.state.lock().unwrap();
+            state.jobs.push_back((task, job));
+            if state.jobs.len() > state.idle && state.workers < self.size {
+                state.workers += 1;
+                let pool = self.clone();
+                thread::spawn(move || pool.work());
+            } else {
+                self.work.notify_one();
+            }
+        }
+        pub fn size(&self) -> usize {
+            self.size
+        }
+        /*
+        Ids of the tasks waiting for a worker
+         */
+        pub fn queued_tasks(&self) -> Vec<u64> {
+            self.state.lock().unwrap().jobs.iter().map(|(task, _)| *task).collect()
+        }
+        pub fn metrics(&self) -> PoolMetrics {
+            let state = self.state.lock().unwrap();
+            PoolMetrics { size: self.size, queued: state.jobs.len(), running: state.running, completed: state.completed }
+        }
+        /*
+        Wait until no task is queued or running
+         */
+        pub fn wait_idle(&self) {
+            let mut state = self.state.lock().unwrap();
+            while !state.jobs.is_empty() || state.running > 0 {
+                state = self.done.wait(state).unwrap();
+            }
+        }
+        /*
+        Stop the workers once the queued tasks ran
+         */
+        pub fn shutdown(&self) {
+            self.state.lock().unwrap().shutdown = true;
+            self.work.notify_all();
+        }
+        /*
+        Worker loop - run queued jobs, wait for more while the pool is not shut down
+         */
+        fn work(&self) {
+            let mut state = self.state.lock().unwrap();
+            loop {
+                if let Some((_, job)) = state.jobs.pop_front() {
+                    state.running += 1;
+                    drop(state);
+                    job();
+                    state = self.state.lock().unwrap();
+                    state.running -= 1;
+                    state.completed += 1;
+                    self.done.notify_all();
+                    continue;
+                }
+                if state.shutdown {
+                    state.workers -= 1;
+                    return;
+                }
+                state.idle += 1;
+                state = self.work.wait(state).unwrap();
+                state.idle -= 1;
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::pool::pool::{Pool, PoolMetrics};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    #[test]
+    fn pool_runs_at_most_size_jobs_at_once() {
+        let pool = Pool::new(2);
+        let (active, most) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        // every job waits for a value from release, so no job finishes before the metrics are checked
+        let (release, gate) = channel();
+        let gate = Arc::new(Mutex::new(gate));
+        for task in 0..8 {
+            let (active, most, gate) = (active.clone(), most.clone(), gate.clone());
+            pool.submit(task, Box::new(move || {
+                most.fetch_max(active.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                gate.lock().unwrap().recv().unwrap();
+                active.fetch_sub(1, Ordering::SeqCst);
+            }));
+        }
+        while pool.metrics().running < 2 {
+            thread::yield_now();
+        }
+        assert_eq!(pool.metrics(), PoolMetrics { size: 2, queued: 6, running: 2, completed: 0 });
+        for _ in 0..8 {
+            release.send(()).unwrap();
+        }
+        pool.wait_idle();
+        assert_eq!(pool.metrics(), PoolMetrics { size: 2, queued: 0, running: 0, completed: 8 });
+        assert_eq!(most.load(Ordering::SeqCst), 2);
+        pool.shutdown();
+    }
+}
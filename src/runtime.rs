@@ -0,0 +1,944 @@
+pub mod runtime {
+    use crate::clock::clock::Clock;
+    use crate::interpreter::interpreter::{ByteCode, ByteCodeDataTypes, ByteCodeError, Machine, Run};
+    use crate::pool::pool::{Pool, PoolMetrics};
+    use crate::task::task::TaskHandle;
+    use crate::waker::waker::Waker;
+    use std::cmp::Reverse;
+    use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+    use std::sync::mpsc::sync_channel;
+    use std::sync::{Arc, Condvar, Mutex, Weak};
+    use std::thread;
+    use std::time::Instant;
+    type Vars = HashMap<&'static str, ByteCodeDataTypes>;
+    type TaskResult = Result<ByteCodeDataTypes, ByteCodeError>;
+    type OnDone = Box<dyn FnOnce(TaskResult, Vars) + Send>;
+    /*
+    How tasks are run - every task on its own OS thread, or as green tasks on the worker threads of a scheduler
+     */
+    #[derive(Clone)]
+    pub enum Runtime {
+        Threads(Arc<Threads>),
+        Green(Arc<Scheduler>),
+    }
+    /*
+    Task that can not continue - its task handle id (0 for the main program), the opcode it waits at
+    and the channels, lock or task it waits for. Reported by the Deadlock error
+     */
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct BlockedTask {
+        pub task: u64,
+        pub opcode: &'static str,
+        pub waiting_on: String,
+    }
+    /*
+    Seeded run whose result or error differs from the run with seed 0
+     */
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    pub struct Divergence {
+        pub seed: u64,
+        pub expected: TaskResult,
+        pub found: TaskResult,
+    }
+    /*
+    Virtual machine executing byte code with the runtime chosen when it was created
+     */
+    pub struct Vm {
+        runtime: Runtime,
+    }
+    /*
+    What a running task can use - the waker to register on what it waits for, the runtime to spawn
+    tasks on, its own handle to check for cancellation and the earliest time it wants to be woken
+    even if nothing else wakes it
+     */
+    pub struct Context<'a> {
+        waker: &'a Waker,
+        runtime: &'a Runtime,
+        task: &'a TaskHandle,
+        deadline: Option<Instant>,
+        blocked: Option<(&'static str, String)>,
+    }
+    /*
+    Tasks of a VM running on OS threads. A task waiting without deadline is registered with its waker,
+    when every live task is registered and none of them was woken the tasks are deadlocked.
+    With a virtual clock tasks waiting for a deadline are registered too, and the clock jumps to the
+    earliest deadline instead. With a pool spawned tasks run on its workers, a task waiting for a worker
+    is live but can not run until a running task finished
+     */
+    pub struct Threads {
+        state: Mutex<ThreadsState>,
+        idle: Condvar,
+        clock: Arc<Clock>,
+        pool: Option<Arc<Pool>>,
+    }
+    struct ThreadsState {
+        live: usize,
+        tasks: HashMap<u64, TaskHandle>,
+        next_key: u64,
+        blocked: HashMap<u64, (BlockedTask, Waker, Option<Instant>)>,
+        pooled: HashSet<u64>,
+        deadlock: Option<Vec<BlockedTask>>,
+    }
+    /*
+    Run state of a green task. A task woken while running is run again once its time slice ends
+     */
+    #[derive(PartialEq)]
+    enum RunState {
+        Idle,
+        Queued,
+        Running,
+        Notified,
+    }
+    /*
+    Green task, the machine is taken out by the worker running it
+     */
+    struct GreenTask {
+        task: u64,
+        handle: TaskHandle,
+        run: RunState,
+        machine: Option<Machine>,
+        waker: Waker,
+        on_done: Option<OnDone>,
+        blocked: Option<(&'static str, String)>,
+    }
+    /*
+    Live tasks, tasks ready to run, timers waking tasks at a deadline and the number of workers running a task.
+    When no task runs or is ready and no timer is set, the live tasks wait for each other and are deadlocked.
+    A seeded scheduler draws every scheduling decision from the random sequence in seed
+     */
+    struct SchedulerState {
+        tasks: HashMap<u64, GreenTask>,
+        run_queue: VecDeque<u64>,
+        timers: BinaryHeap<Reverse<(Instant, u64)>>,
+        running: usize,
+        next_id: u64,
+        shutdown: bool,
+        seed: Option<u64>,
+    }
+    /*
+    M:N scheduler running green tasks cooperatively on a fixed number of worker threads.
+    A task gives its worker back when it has to wait, yields or used up its time slice
+     */
+    pub struct Scheduler {
+        state: Mutex<SchedulerState>,
+        work: Condvar,
+        idle: Condvar,
+        clock: Arc<Clock>,
+    }
+
+    #[allow(dead_code)]
+    impl Vm {
+        /*
+        Every spawned task runs on a new OS thread
+         */
+        pub fn new() -> Vm {
+            Vm::threads(None)
+        }
+        /*
+        Spawned tasks run on a pool of at most size OS threads, tasks spawned while every worker is busy
+        wait in a queue until a running task finished. The main program runs on the thread calling execute
+         */
+        pub fn pooled(size: usize) -> Vm {
+            Vm::threads(Some(Pool::new(size)))
+        }
+        fn threads(pool: Option<Arc<Pool>>) -> Vm {
+            let threads = Threads {
+                state: Mutex::new(ThreadsState {
+                    live: 0,
+                    tasks: HashMap::new(),
+                    next_key: 0,
+                    blocked: HashMap::new(),
+                    pooled: HashSet::new(),
+                    deadlock: None,
+                }),
+                idle: Condvar::new(),
+                clock: Arc::new(Clock::new()),
+                pool,
+            };
+            Vm { runtime: Runtime::Threads(Arc::new(threads)) }
+        }
+        /*
+        Queued, running and completed tasks of the pool, None if the VM has no pool
+         */
+        pub fn pool_metrics(&self) -> Option<PoolMetrics> {
+            match &self.runtime {
+                Runtime::Threads(threads) => threads.pool.as_ref().map(|pool| pool.metrics()),
+                Runtime::Green(_) => None,
+            }
+        }
+        /*
+        Every task, including the main program, runs as a green task on the given number of worker threads
+         */
+        pub fn green(workers: usize) -> Vm {
+            Vm { runtime: Runtime::Green(Scheduler::start(workers.max(1), None)) }
+        }
+        /*
+        Every task runs as a green task on the thread calling execute. Which ready task runs next and whether
+        a task is switched out at channel, lock, atomic and yield points is chosen by the seed,
+        so the same seed gives the same interleaving (as long as tasks do not wait for timeouts)
+         */
+        pub fn seeded(seed: u64) -> Vm {
+            Vm { runtime: Runtime::Green(Scheduler::start(0, Some(seed))) }
+        }
+        /*
+        Sleeps and timers advance a virtual clock instead of waiting: when every task waits, the clock jumps
+        to the earliest deadline and wakes the tasks waiting for it. NOW reads the virtual time
+         */
+        pub fn with_virtual_clock(self) -> Vm {
+            self.runtime.clock().set_virtual();
+            self
+        }
+        /*
+        Execute the byte code with seeds 0 to seeds - 1, returns the first seed whose result differs from seed 0.
+        Errors are compared by kind only, as they may name tasks and channels
+         */
+        pub fn explore(code: Vec<ByteCode>, stack: Vec<ByteCodeDataTypes>, global_vars: Vars,
+                       functions: HashMap<&'static str, Vec<ByteCode>>, seeds: u64) -> Option<Divergence> {
+            let run = |seed| Vm::seeded(seed).execute(code.clone(), stack.clone(), global_vars.clone(), functions.clone()).0;
+            let expected = run(0);
+            for seed in 1..seeds {
+                let found = run(seed);
+                let same = match (&expected, &found) {
+                    (Ok(expected), Ok(found)) => expected == found,
+                    (Err(expected), Err(found)) => std::mem::discriminant(expected) == std::mem::discriminant(found),
+                    _ => false,
+                };
+                if !same {
+                    return Some(Divergence { seed, expected, found });
+                }
+            }
+            None
+        }
+        /*
+        Interpret and execute the byte code, returns the result and the variables of the main program
+         */
+        pub fn execute(&self, code: Vec<ByteCode>, stack: Vec<ByteCodeDataTypes>, global_vars: Vars,
+                       functions: HashMap<&'static str, Vec<ByteCode>>) -> (TaskResult, Vars) {
+            let machine = Machine::new(code, stack, global_vars, functions);
+            match &self.runtime {
+                Runtime::Threads(threads) => {
+                    let handle = TaskHandle::new();
+                    let key = threads.enter(&handle);
+                    let (result, vars) = drive(machine, &self.runtime, threads, key, &handle, 0);
+                    handle.complete(result.clone());
+                    threads.exit(key);
+                    (result, vars)
+                },
+                Runtime::Green(scheduler) => {
+                    let (tx, rx) = sync_channel(1);
+                    scheduler.spawn(machine, TaskHandle::new(), 0, Box::new(move |result, vars| {
+                        let _ = tx.send((result, vars));
+                    }));
+                    if scheduler.is_seeded() {
+                        // a seeded scheduler has no workers, the calling thread runs the tasks
+                        while let Some(task) = scheduler.next_task() {
+                            scheduler.run(&self.runtime, task);
+                            if let Ok(done) = rx.try_recv() {
+                                return done;
+                            }
+                        }
+                    }
+                    rx.recv().unwrap()
+                },
+            }
+        }
+        /*
+        Cancel every task, including a main program still running, and wait until all of them finished
+         */
+        pub fn shutdown(&self) {
+            match &self.runtime {
+                Runtime::Threads(threads) => {
+                    threads.cancel_all();
+                    threads.wait_idle();
+                    if let Some(pool) = &threads.pool {
+                        pool.wait_idle();
+                    }
+                },
+                Runtime::Green(scheduler) => {
+                    scheduler.cancel_all();
+                    if scheduler.is_seeded() {
+                        while let Some(task) = scheduler.next_task() {
+                            scheduler.run(&self.runtime, task);
+                        }
+                    }
+                    scheduler.wait_idle();
+                },
+            }
+        }
+    }
+    impl Default for Vm {
+        fn default() -> Vm {
+            Vm::new()
+        }
+    }
+    impl Drop for Vm {
+        fn drop(&mut self) {
+            match &self.runtime {
+                Runtime::Threads(threads) => {
+                    if let Some(pool) = &threads.pool {
+                        pool.shutdown();
+                    }
+                },
+                Runtime::Green(scheduler) => scheduler.shutdown(),
+            }
+        }
+    }
+
+    impl Runtime {
+        pub fn clock(&self) -> &Arc<Clock> {
+            match self {
+                Runtime::Threads(threads) => &threads.clock,
+                Runtime::Green(scheduler) => &scheduler.clock,
+            }
+        }
+        /*
+        Start running task and return its handle immediately
+         */
+        pub fn spawn(&self, machine: Machine) -> TaskHandle {
+            let handle = TaskHandle::new();
+            let task_handle = handle.clone();
+            match self {
+                Runtime::Threads(threads) => {
+                    // the task is live from now on, otherwise the spawning task could be taken as deadlocked
+                    let key = threads.enter(&handle);
+                    let (runtime, pool) = (self.clone(), threads.pool.clone());
+                    let threads = threads.clone();
+                    let pooled = pool.is_some();
+                    let run = move || {
+                        if pooled {
+                            threads.state.lock().unwrap().pooled.insert(key);
+                        }
+                        // a task cancelled while it waited for a pool worker does not start
+                        let (result, vars) = if task_handle.is_cancelled() {
+                            (Err(ByteCodeError::Cancelled), HashMap::new())
+                        } else {
+                            drive(machine, &runtime, &threads, key, &task_handle, task_handle.id())
+                        };
+                        drop(vars);
+                        task_handle.complete(result);
+                        threads.exit(key);
+                    };
+                    match pool {
+                        Some(pool) => pool.submit(handle.id(), Box::new(run)),
+                        None => {
+                            thread::spawn(run);
+                        },
+                    }
+                },
+                Runtime::Green(scheduler) => {
+                    scheduler.spawn(machine, handle.clone(), handle.id(), Box::new(move |result, _| task_handle.complete(result)));
+                },
+            }
+            handle
+        }
+    }
+
+    impl<'a> Context<'a> {
+        pub fn new(waker: &'a Waker, runtime: &'a Runtime, task: &'a TaskHandle) -> Context<'a> {
+            Context { waker, runtime, task, deadline: None, blocked: None }
+        }
+        pub fn waker(&self) -> &'a Waker {
+            self.waker
+        }
+        pub fn spawn(&self, machine: Machine) -> TaskHandle {
+            self.runtime.spawn(machine)
+        }
+        pub fn is_cancelled(&self) -> bool {
+            self.task.is_cancelled()
+        }
+        pub fn task(&self) -> &'a TaskHandle {
+            self.task
+        }
+        /*
+        Wake the task at deadline at the latest
+         */
+        pub fn wait_until(&mut self, deadline: Instant) {
+            self.deadline = Some(self.deadline.map_or(deadline, |d| d.min(deadline)));
+        }
+        pub fn deadline(&self) -> Option<Instant> {
+            self.deadline
+        }
+        pub fn clock(&self) -> &'a Arc<Clock> {
+            self.runtime.clock()
+        }
+        /*
+        Current time on the clock of the VM, deadlines are relative to it
+         */
+        pub fn now(&self) -> Instant {
+            self.runtime.clock().now()
+        }
+        /*
+        Record the opcode the task waits at and what it waits for
+         */
+        pub fn blocked_on(&mut self, opcode: &'static str, waiting_on: String) {
+            self.blocked = Some((opcode, waiting_on));
+        }
+        fn take_blocked(&mut self) -> Option<(&'static str, String)> {
+            self.blocked.take()
+        }
+        /*
+        Whether a seeded scheduler switches to another task before the next instruction
+         */
+        pub fn preempt(&self) -> bool {
+            match self.runtime {
+                Runtime::Green(scheduler) => scheduler.state.lock().unwrap().random(2) == Some(0),
+                Runtime::Threads(_) => false,
+            }
+        }
+        /*
+        Index below len chosen by a seeded scheduler, None if the runtime is not seeded
+         */
+        pub fn choose(&self, len: usize) -> Option<usize> {
+            match self.runtime {
+                Runtime::Green(scheduler) => scheduler.state.lock().unwrap().random(len),
+                Runtime::Threads(_) => None,
+            }
+        }
+    }
+    /*
+    Run task on the current thread, parking the thread while the task waits
+     */
+    fn drive(mut machine: Machine, runtime: &Runtime, threads: &Threads, key: u64, handle: &TaskHandle,
+             task: u64) -> (TaskResult, Vars) {
+        let waker = Waker::current();
+        handle.attach(&waker);
+        loop {
+            waker.renew();
+            let mut cx = Context::new(&waker, runtime, handle);
+            match machine.resume(&mut cx) {
+                Run::Done(result, vars) => {
+                    waker.renew();
+                    return (result, vars);
+                },
+                Run::Yield => thread::yield_now(),
+                Run::Pending => match (cx.deadline(), cx.take_blocked()) {
+                    (None, None) => waker.wait(None),
+                    (Some(deadline), _) if !threads.clock.is_virtual() => waker.wait(Some(deadline)),
+                    (deadline, blocked) => {
+                        let (opcode, waiting_on) = blocked.unwrap_or_default();
+                        if let Some(tasks) = threads.wait(key, BlockedTask { task, opcode, waiting_on }, deadline, &waker) {
+                            waker.renew();
+                            return (Err(ByteCodeError::Deadlock(tasks)), HashMap::new());
+                        }
+                    },
+                },
+            }
+        }
+    }
+
+    impl Threads {
+        /*
+        Register new live task and return its key
+         */
+        fn enter(&self, handle: &TaskHandle) -> u64 {
+            let mut state = self.state.lock().unwrap();
+            state.live += 1;
+            state.next_key += 1;
+            let key = state.next_key;
+            state.tasks.insert(key, handle.clone());
+            key
+        }
+        /*
+        Task finished, must be called after everything it held (e.g. channel end points) was dropped
+         */
+        fn exit(&self, key: u64) {
+            let mut state = self.state.lock().unwrap();
+            state.live -= 1;
+            state.tasks.remove(&key);
+            state.pooled.remove(&key);
+            if state.live == 0 {
+                state.deadlock = None;
+                self.idle.notify_all();
+            } else {
+                // the other tasks may all wait since before this one finished
+                self.check_blocked(&mut state);
+            }
+        }
+        fn cancel_all(&self) {
+            let tasks: Vec<TaskHandle> = self.state.lock().unwrap().tasks.values().cloned().collect();
+            for task in tasks {
+                task.cancel();
+            }
+        }
+        fn wait_idle(&self) {
+            let mut state = self.state.lock().unwrap();
+            while state.live > 0 {
+                state = self.idle.wait(state).unwrap();
+            }
+        }
+        /*
+        When every live task waits (for a wake or a pool worker) and none of them was woken, jump virtual time
+        to the earliest deadline and wake the tasks waiting for it, without deadline the tasks are deadlocked
+         */
+        fn check_blocked(&self, state: &mut ThreadsState) {
+            // queued tasks can only run when a worker is free, which is not the case when every worker runs a task
+            let queued = match &self.pool {
+                Some(pool) if state.pooled.len() >= pool.size() => pool.queued_tasks(),
+                _ => Vec::new(),
+            };
+            // a woken task stays registered until it continues, so a wake in flight is seen here
+            if state.deadlock.is_some() || state.blocked.len() + queued.len() != state.live
+                || state.blocked.values().any(|(_, w, _)| w.is_notified()) {
+                return;
+            }
+            match state.blocked.values().filter_map(|(_, _, deadline)| *deadline).min() {
+                Some(next) => {
+                    self.clock.advance_to(next);
+                    for (_, waker, _) in state.blocked.values().filter(|(_, _, deadline)| *deadline == Some(next)) {
+                        waker.wake();
+                    }
+                },
+                None => {
+                    let mut tasks: Vec<BlockedTask> = state.blocked.values().map(|(task, _, _)| task.clone()).collect();
+                    tasks.extend(queued.into_iter().map(|task| BlockedTask { task, opcode: "SPAWN", waiting_on: "pool worker".to_string() }));
+                    tasks.sort_by_key(|task| task.task);
+                    for (_, waker, _) in state.blocked.values() {
+                        waker.wake();
+                    }
+                    state.deadlock = Some(tasks);
+                },
+            }
+        }
+        /*
+        Park the thread until the task is woken (or virtual time reached deadline), returns the blocked tasks
+        when they are deadlocked
+         */
+        fn wait(&self, key: u64, blocked: BlockedTask, deadline: Option<Instant>, waker: &Waker) -> Option<Vec<BlockedTask>> {
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.deadlock.is_none() {
+                    state.blocked.insert(key, (blocked, waker.clone(), deadline));
+                    self.check_blocked(&mut state);
+                }
+                if state.deadlock.is_some() {
+                    state.blocked.remove(&key);
+                    return state.deadlock.clone();
+                }
+            }
+            waker.wait_notified();
+            let deadlock = {
+                let mut state = self.state.lock().unwrap();
+                state.blocked.remove(&key);
+                state.deadlock.clone()
+            };
+            waker.clear();
+            deadlock
+        }
+    }
+
+    impl SchedulerState {
+        /*
+        Next index below len from the splitmix64 sequence of a seeded scheduler
+         */
+        fn random(&mut self, len: usize) -> Option<usize> {
+            let seed = self.seed.as_mut()?;
+            *seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = *seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            Some(((z ^ (z >> 31)) % len.max(1) as u64) as usize)
+        }
+        /*
+        Take the next ready task, a seeded scheduler picks one of them by id so the order tasks were woken in does not matter
+         */
+        fn pop_ready(&mut self) -> Option<u64> {
+            if self.seed.is_none() || self.run_queue.len() < 2 {
+                return self.run_queue.pop_front();
+            }
+            let mut ready: Vec<u64> = self.run_queue.iter().copied().collect();
+            ready.sort_unstable();
+            let id = ready[self.random(ready.len()).unwrap()];
+            self.run_queue.retain(|&queued| queued != id);
+            Some(id)
+        }
+        fn wake(&mut self, id: u64) -> bool {
+            match self.tasks.get_mut(&id) {
+                Some(task) if task.run == RunState::Idle => {
+                    task.run = RunState::Queued;
+                    self.run_queue.push_back(id);
+                    true
+                },
+                Some(task) if task.run == RunState::Running => {
+                    task.run = RunState::Notified;
+                    false
+                },
+                _ => false,
+            }
+        }
+    }
+
+    impl Scheduler {
+        fn start(workers: usize, seed: Option<u64>) -> Arc<Scheduler> {
+            let scheduler = Arc::new(Scheduler {
+                state: Mutex::new(SchedulerState {
+                    tasks: HashMap::new(),
+                    run_queue: VecDeque::new(),
+                    timers: BinaryHeap::new(),
+                    running: 0,
+                    next_id: 1,
+                    shutdown: false,
+                    seed,
+                }),
+                work: Condvar::new(),
+                idle: Condvar::new(),
+                clock: Arc::new(Clock::new()),
+            });
+            for _ in 0..workers {
+                let runtime = Runtime::Green(scheduler.clone());
+                thread::spawn(move || {
+                    if let Runtime::Green(scheduler) = &runtime {
+                        scheduler.work(&runtime);
+                    }
+                });
+            }
+            scheduler
+        }
+        fn spawn(self: &Arc<Scheduler>, machine: Machine, handle: TaskHandle, task: u64, on_done: OnDone) {
+            let mut state = self.state.lock().unwrap();
+            let id = state.next_id;
+            state.next_id += 1;
+            let scheduler = Arc::downgrade(self);
+            let waker = Waker::from_fn(move || {
+                if let Some(scheduler) = Weak::upgrade(&scheduler) {
+                    scheduler.wake(id);
+                }
+            });
+            handle.attach(&waker);
+            state.tasks.insert(id, GreenTask {
+                task,
+                handle,
+                run: RunState::Queued,
+                machine: Some(machine),
+                waker,
+                on_done: Some(on_done),
+                blocked: None,
+            });
+            state.run_queue.push_back(id);
+            self.work.notify_one();
+        }
+        fn wake(&self, id: u64) {
+            if self.state.lock().unwrap().wake(id) {
+                self.work.notify_one();
+            }
+        }
+        fn cancel_all(&self) {
+            let tasks: Vec<TaskHandle> = self.state.lock().unwrap().tasks.values().map(|task| task.handle.clone()).collect();
+            for task in tasks {
+                task.cancel();
+            }
+        }
+        /*
+        Wait until every task finished
+         */
+        fn wait_idle(&self) {
+            let mut state = self.state.lock().unwrap();
+            while !state.tasks.is_empty() || state.running > 0 {
+                state = self.idle.wait(state).unwrap();
+            }
+        }
+        fn is_seeded(&self) -> bool {
+            self.state.lock().unwrap().seed.is_some()
+        }
+        /*
+        Stop the workers, tasks that did not finish are dropped
+         */
+        fn shutdown(&self) {
+            let tasks = {
+                let mut state = self.state.lock().unwrap();
+                state.shutdown = true;
+                state.run_queue.clear();
+                std::mem::take(&mut state.tasks)
+            };
+            self.work.notify_all();
+            // dropping tasks closes their channels, which wakes other tasks through the scheduler lock
+            drop(tasks);
+        }
+        /*
+        Worker loop - take the next ready task and run it for one time slice
+         */
+        fn work(&self, runtime: &Runtime) {
+            while let Some(task) = self.next_task() {
+                self.run(runtime, task);
+            }
+        }
+        /*
+        Run task for one time slice and put it back according to how the slice ended
+         */
+        fn run(&self, runtime: &Runtime, (id, mut machine, waker, handle): (u64, Machine, Waker, TaskHandle)) {
+            waker.renew();
+            let mut cx = Context::new(&waker, runtime, &handle);
+            let run = machine.resume(&mut cx);
+            let (deadline, blocked) = (cx.deadline(), cx.take_blocked());
+            let mut state = self.state.lock().unwrap();
+            if state.shutdown {
+                return;
+            }
+            match run {
+                Run::Done(result, vars) => {
+                    let task = state.tasks.remove(&id).unwrap();
+                    drop(state);
+                    waker.renew();
+                    drop(machine);
+                    (task.on_done.unwrap())(result, vars);
+                    // the task counts as running until the tasks it woke are queued
+                    self.state.lock().unwrap().running -= 1;
+                    self.idle.notify_all();
+                },
+                Run::Yield => {
+                    state.running -= 1;
+                    let task = state.tasks.get_mut(&id).unwrap();
+                    task.machine = Some(machine);
+                    task.run = RunState::Queued;
+                    state.run_queue.push_back(id);
+                    self.work.notify_one();
+                },
+                Run::Pending => {
+                    state.running -= 1;
+                    let task = state.tasks.get_mut(&id).unwrap();
+                    task.machine = Some(machine);
+                    task.blocked = blocked;
+                    if task.run == RunState::Notified {
+                        task.run = RunState::Queued;
+                        state.run_queue.push_back(id);
+                        self.work.notify_one();
+                    } else {
+                        task.run = RunState::Idle;
+                    }
+                    if let Some(deadline) = deadline {
+                        state.timers.push(Reverse((deadline, id)));
+                        // a worker waiting for a later timer has to wait for this one
+                        self.work.notify_all();
+                    }
+                },
+            }
+        }
+        /*
+        Fail every task with a Deadlock error listing what each of them waits for
+         */
+        fn fail_deadlocked(tasks: Vec<GreenTask>) {
+            let mut blocked: Vec<BlockedTask> = tasks.iter().map(|task| {
+                let (opcode, waiting_on) = task.blocked.clone().unwrap_or_default();
+                BlockedTask { task: task.task, opcode, waiting_on }
+            }).collect();
+            blocked.sort_by_key(|task| task.task);
+            let mut tasks = tasks;
+            // tasks are woken in a fixed order, so a seeded scheduler continues the same way every run
+            tasks.sort_by_key(|task| task.task);
+            for mut task in tasks {
+                drop(task.machine.take());
+                task.waker.renew();
+                (task.on_done.unwrap())(Err(ByteCodeError::Deadlock(blocked.clone())), HashMap::new());
+            }
+        }
+        /*
+        Wait for a task that is ready to run, firing timers that expired. None once the scheduler is shut down
+         */
+        fn next_task(&self) -> Option<(u64, Machine, Waker, TaskHandle)> {
+            let mut state = self.state.lock().unwrap();
+            loop {
+                if state.shutdown {
+                    return None;
+                }
+                let now = self.clock.now();
+                while let Some(&Reverse((deadline, id))) = state.timers.peek() {
+                    if deadline > now {
+                        break;
+                    }
+                    state.timers.pop();
+                    state.wake(id);
+                }
+                if let Some(id) = state.pop_ready() {
+                    state.running += 1;
+                    let task = state.tasks.get_mut(&id).unwrap();
+                    task.run = RunState::Running;
+                    return Some((id, task.machine.take().unwrap(), task.waker.clone(), task.handle.clone()));
+                }
+                if state.running == 0 && state.timers.is_empty() && !state.tasks.is_empty() {
+                    let tasks = std::mem::take(&mut state.tasks).into_values().collect();
+                    drop(state);
+                    Scheduler::fail_deadlocked(tasks);
+                    self.idle.notify_all();
+                    state = self.state.lock().unwrap();
+                    continue;
+                }
+                if let Some(&Reverse((deadline, _))) = state.timers.peek().filter(|_| state.running == 0 && self.clock.is_virtual()) {
+                    // every task waits for a timer, virtual time jumps to the earliest one
+                    self.clock.advance_to(deadline);
+                    continue;
+                }
+                if state.seed.is_some() && state.timers.is_empty() {
+                    // nothing left that the thread running a seeded scheduler could wait for
+                    return None;
+                }
+                state = match state.timers.peek().filter(|_| !self.clock.is_virtual()) {
+                    Some(&Reverse((deadline, _))) => self.work.wait_timeout(state, deadline - now).unwrap().0,
+                    None => self.work.wait(state).unwrap(),
+                };
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::interpreter::{ByteCode, ByteCodeDataTypes, ByteCodeError};
+    use crate::pool::pool::PoolMetrics;
+    use crate::runtime::runtime::Vm;
+    use std::collections::HashMap;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    fn deadlock(vm: &Vm, code: Vec<ByteCode>) -> Vec<(u64, &'static str)> {
+        match vm.execute(code, Vec::new(), HashMap::new(), HashMap::new()).0 {
+            Err(ByteCodeError::Deadlock(tasks)) => tasks.iter().map(|task| (task.task, task.opcode)).collect(),
+            other => panic!("expected deadlock, got {:?}", other),
+        }
+    }
+    /*
+    Two tasks increment counter "hits" by loading and storing it, an update is lost when they interleave
+     */
+    fn racy_increments(atomic_add: bool) -> Vec<ByteCode> {
+        let increment = if atomic_add {
+            vec![ByteCode::LoadVar(1), ByteCode::AtomicAdd("hits"), ByteCode::Return]
+        } else {
+            vec![ByteCode::AtomicLoad("hits"), ByteCode::LoadVar(1), ByteCode::Add, ByteCode::AtomicStore("hits"), ByteCode::Return]
+        };
+        vec![ByteCode::LoadVar(0), ByteCode::Atomic("hits"),
+             ByteCode::Spawn(increment.clone(), vec![]), ByteCode::Spawn(increment, vec![]), ByteCode::List(2), ByteCode::JoinAll,
+             ByteCode::AtomicLoad("hits"), ByteCode::ReturnValue]
+    }
+    #[test]
+    fn same_seed_gives_same_interleaving() {
+        for seed in 0..20 {
+            let run = || Vm::seeded(seed).execute(racy_increments(false), Vec::new(), HashMap::new(), HashMap::new()).0.unwrap();
+            assert_eq!(run(), run());
+        }
+    }
+    #[test]
+    fn explore_finds_seed_losing_an_update() {
+        let divergence = Vm::explore(racy_increments(false), Vec::new(), HashMap::new(), HashMap::new(), 50).unwrap();
+        assert!(divergence.seed > 0);
+        assert_ne!(divergence.expected.unwrap(), divergence.found.unwrap());
+        assert!(Vm::explore(racy_increments(true), Vec::new(), HashMap::new(), HashMap::new(), 50).is_none());
+    }
+    fn forever() -> ByteCode {
+        ByteCode::Loop(vec![ByteCode::LoadVar(1), ByteCode::LoadVar(2), ByteCode::LessThan],
+                       vec![ByteCode::LoadVar(0), ByteCode::WriteVar("x")])
+    }
+    #[test]
+    fn cancelled_tasks_fail_join_with_cancelled() {
+        let receive = vec![ByteCode::ReceiveChannel(Some("rx")), ByteCode::ReturnValue];
+        let code = vec![ByteCode::Channel(None, Some(("tx", "rx"))),
+                        ByteCode::Spawn(vec![forever()], vec![]), ByteCode::WriteVar("looping"),
+                        ByteCode::Spawn(receive, vec![]), ByteCode::WriteVar("waiting"),
+                        ByteCode::Cancel(Some("looping")), ByteCode::Cancel(Some("waiting")),
+                        ByteCode::ReadVar("looping"), ByteCode::ReadVar("waiting"), ByteCode::List(2), ByteCode::JoinAll,
+                        ByteCode::ReturnValue];
+        for vm in [Vm::new(), Vm::green(2), Vm::seeded(5)] {
+            let (result, _) = vm.execute(code.clone(), Vec::new(), HashMap::new(), HashMap::new());
+            assert!(matches!(result, Err(ByteCodeError::Cancelled)), "{:?}", result);
+            let (result, _) = vm.execute(vec![ByteCode::Spawn(vec![ByteCode::Sleep(60)], vec![]), ByteCode::WriteVar("task"),
+                                              ByteCode::Cancel(Some("task")), ByteCode::Join(Some("task")), ByteCode::ReturnValue],
+                                         Vec::new(), HashMap::new(), HashMap::new());
+            assert!(matches!(result, Err(ByteCodeError::Cancelled)), "{:?}", result);
+        }
+    }
+    #[test]
+    fn shutdown_cancels_running_program() {
+        for vm in [Vm::new(), Vm::green(2)] {
+            thread::scope(|scope| {
+                let main = scope.spawn(|| vm.execute(vec![ByteCode::Spawn(vec![forever()], vec![]), forever(), ByteCode::ReturnValue],
+                                                     Vec::new(), HashMap::new(), HashMap::new()).0);
+                thread::sleep(Duration::from_millis(50));
+                vm.shutdown();
+                assert!(matches!(main.join().unwrap(), Err(ByteCodeError::Cancelled)));
+            });
+        }
+    }
+    #[test]
+    fn receiving_from_own_channel_deadlocks() {
+        let code = vec![ByteCode::Channel(None, Some(("tx", "rx"))), ByteCode::ReceiveChannel(Some("rx")), ByteCode::ReturnValue];
+        assert_eq!(deadlock(&Vm::new(), code.clone()), vec![(0, "RECEIVE_CHANNEL")]);
+        assert_eq!(deadlock(&Vm::green(1), code.clone()), vec![(0, "RECEIVE_CHANNEL")]);
+        assert_eq!(deadlock(&Vm::seeded(3), code), vec![(0, "RECEIVE_CHANNEL")]);
+    }
+    #[test]
+    fn tasks_waiting_on_each_other_deadlock() {
+        let code = vec![ByteCode::Channel(None, Some(("atx", "arx"))), ByteCode::Channel(None, Some(("btx", "brx"))),
+                        ByteCode::Spawn(vec![ByteCode::ReceiveChannel(Some("arx")), ByteCode::SendChannel(Some("btx")), ByteCode::Return], vec![]),
+                        ByteCode::WriteVar("task"), ByteCode::ReceiveChannel(Some("brx")), ByteCode::SendChannel(Some("atx")),
+                        ByteCode::Join(Some("task")), ByteCode::ReturnValue];
+        for vm in [Vm::new(), Vm::green(2)] {
+            let tasks = deadlock(&vm, code.clone());
+            assert_eq!(tasks.len(), 2);
+            assert_eq!(tasks[0], (0, "RECEIVE_CHANNEL"));
+            assert_eq!(tasks[1].1, "RECEIVE_CHANNEL");
+        }
+    }
+    #[test]
+    fn virtual_clock_orders_sleeps_without_waiting() {
+        let sleeper = |seconds| vec![ByteCode::Sleep(seconds), ByteCode::LoadVar(seconds as i64), ByteCode::SendChannel(Some("tx")),
+                                     ByteCode::Now, ByteCode::ReturnValue];
+        let code = vec![ByteCode::Channel(None, Some(("tx", "rx"))),
+                        ByteCode::Spawn(sleeper(30), vec![]), ByteCode::Spawn(sleeper(10), vec![]), ByteCode::Spawn(sleeper(20), vec![]),
+                        ByteCode::List(3), ByteCode::JoinAll, ByteCode::WriteVar("times"),
+                        ByteCode::ReceiveChannel(Some("rx")), ByteCode::ReceiveChannel(Some("rx")), ByteCode::ReceiveChannel(Some("rx")),
+                        ByteCode::List(3), ByteCode::ReadVar("times"), ByteCode::SleepMs(500), ByteCode::Now, ByteCode::List(3),
+                        ByteCode::ReturnValue];
+        let started = Instant::now();
+        for vm in [Vm::new(), Vm::green(2), Vm::seeded(7)] {
+            let (result, _) = vm.with_virtual_clock().execute(code.clone(), Vec::new(), HashMap::new(), HashMap::new());
+            let list = |items: [i64; 3]| ByteCodeDataTypes::List(items.iter().map(|&i| ByteCodeDataTypes::Integer64(i)).collect());
+            assert_eq!(result.unwrap(), ByteCodeDataTypes::List(vec![list([10, 20, 30]), list([30000, 10000, 20000]),
+                                                                     ByteCodeDataTypes::Integer64(30500)]));
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+    #[test]
+    fn pool_queues_tasks_beyond_its_size() {
+        let mut code = vec![ByteCode::Channel(None, Some(("tx", "rx")))];
+        code.extend((0..6).map(|_| ByteCode::Spawn(vec![ByteCode::ReceiveChannel(Some("rx")), ByteCode::ReturnValue], vec![])));
+        code.push(ByteCode::List(6));
+        code.extend((1..=6).flat_map(|i| [ByteCode::LoadVar(i), ByteCode::SendChannel(Some("tx"))]));
+        code.extend([ByteCode::JoinAll, ByteCode::ReturnValue]);
+        let vm = Vm::pooled(2);
+        let mut received = match vm.execute(code, Vec::new(), HashMap::new(), HashMap::new()).0.unwrap() {
+            ByteCodeDataTypes::List(items) => items,
+            other => panic!("expected list, got {:?}", other),
+        };
+        received.sort_by_key(|item| format!("{:?}", item));
+        assert_eq!(received, (1..=6).map(ByteCodeDataTypes::Integer64).collect::<Vec<_>>());
+        vm.shutdown();
+        assert_eq!(vm.pool_metrics(), Some(PoolMetrics { size: 2, queued: 0, running: 0, completed: 6 }));
+        assert_eq!(Vm::new().pool_metrics(), None);
+    }
+    #[test]
+    fn starved_pool_reports_queued_tasks_as_deadlocked() {
+        let code = vec![ByteCode::Channel(None, Some(("tx", "rx"))),
+                        ByteCode::Spawn(vec![ByteCode::ReceiveChannel(Some("rx")), ByteCode::ReturnValue], vec![]), ByteCode::WriteVar("task"),
+                        ByteCode::Spawn(vec![ByteCode::LoadVar(1), ByteCode::SendChannel(Some("tx")), ByteCode::Return], vec![]),
+                        ByteCode::Join(Some("task")), ByteCode::ReturnValue];
+        let tasks = deadlock(&Vm::pooled(1), code.clone());
+        assert_eq!(tasks.iter().map(|task| task.1).collect::<Vec<_>>(), vec!["JOIN", "RECEIVE_CHANNEL", "SPAWN"]);
+        assert_eq!(Vm::pooled(2).execute(code, Vec::new(), HashMap::new(), HashMap::new()).0.unwrap(), ByteCodeDataTypes::Integer64(1));
+    }
+    #[test]
+    fn thousands_of_green_tasks_wait_on_two_workers() {
+        let task_code = vec![ByteCode::ReceiveChannel(Some("rx")), ByteCode::Yield, ByteCode::ReturnValue];
+        let spawn_loop = ByteCode::Loop(
+            vec![ByteCode::ReadVar("i"), ByteCode::LoadVar(2000), ByteCode::LessThan],
+            vec![ByteCode::ReadVar("tasks"), ByteCode::Spawn(task_code, vec![]), ByteCode::ListPush, ByteCode::WriteVar("tasks"),
+                 ByteCode::ReadVar("i"), ByteCode::LoadVar(1), ByteCode::Add, ByteCode::WriteVar("i")]);
+        let send_loop = ByteCode::Loop(
+            vec![ByteCode::ReadVar("i"), ByteCode::LoadVar(0), ByteCode::GreaterThan],
+            vec![ByteCode::ReadVar("i"), ByteCode::SendChannel(Some("tx")),
+                 ByteCode::ReadVar("i"), ByteCode::LoadVar(1), ByteCode::Subtract, ByteCode::WriteVar("i")]);
+        let vm = Vm::green(2);
+        let (result, _) = vm.execute(vec![ByteCode::Channel(None, Some(("tx", "rx"))), ByteCode::List(0), ByteCode::WriteVar("tasks"),
+                                          ByteCode::LoadVar(0), ByteCode::WriteVar("i"), spawn_loop, send_loop,
+                                          ByteCode::ReadVar("tasks"), ByteCode::JoinAll, ByteCode::ListLen, ByteCode::ReturnValue],
+                                     Vec::new(),
+                                     HashMap::new(),
+                                     HashMap::new());
+        assert_eq!(result.unwrap(), ByteCodeDataTypes::Integer64(2000));
+    }
+}
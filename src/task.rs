@@ -0,0 +1,134 @@
+pub mod task {
+    use crate::channel::channel::{channel, ChannelReceiver, ChannelSender};
+    use crate::interpreter::interpreter::{ByteCodeDataTypes, ByteCodeError};
+    use crate::waker::waker::{WaitQueue, Waker};
+    use std::fmt;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    /*
+    Counter to give every spawned task a unique id
+     */
+    static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
+    /*
+    Result of a finished task, shared between the task and every copy of its handle.
+    A cancelled task is woken with its waker and stops at the next cancellation check.
+    The mailbox is an unbounded channel that is closed when the task finishes
+     */
+    struct TaskState {
+        id: u64,
+        result: Mutex<(Option<Result<ByteCodeDataTypes, ByteCodeError>>, WaitQueue)>,
+        cancelled: AtomicBool,
+        waker: Mutex<Option<Waker>>,
+        mailbox: (ChannelSender, ChannelReceiver),
+    }
+    /*
+    Handle to a spawned task, pushed to stack by SPAWN and consumed by JOIN
+     */
+    #[derive(Clone)]
+    pub struct TaskHandle {
+        state: Arc<TaskState>,
+    }
+
+    impl TaskHandle {
+        /*
+        Handle of a task that is run by the caller, which has to complete it
+         */
+        pub fn new() -> TaskHandle {
+            TaskHandle {
+                state: Arc::new(TaskState {
+                    id: NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed),
+                    result: Mutex::new((None, WaitQueue::default())),
+                    cancelled: AtomicBool::new(false),
+                    waker: Mutex::new(None),
+                    mailbox: channel(None),
+                }),
+            }
+        }
+        pub fn id(&self) -> u64 {
+            self.state.id
+        }
+        pub fn complete(&self, result: Result<ByteCodeDataTypes, ByteCodeError>) {
+            // closed before joiners are woken, so they see sending to the finished task fail
+            self.state.mailbox.1.close();
+            let mut state = self.state.result.lock().unwrap();
+            state.0 = Some(result);
+            state.1.wake_all();
+        }
+        /*
+        Result of the task if it finished, otherwise register waker (when given) and return None
+         */
+        pub fn poll_join(&self, waker: Option<&Waker>) -> Option<Result<ByteCodeDataTypes, ByteCodeError>> {
+            let mut state = self.state.result.lock().unwrap();
+            if state.0.is_none() {
+                if let Some(waker) = waker {
+                    state.1.register(waker);
+                }
+            }
+            state.0.clone()
+        }
+        /*
+        Waker of the running task, used to wake it when it is cancelled while waiting
+         */
+        pub fn attach(&self, waker: &Waker) {
+            *self.state.waker.lock().unwrap() = Some(waker.clone());
+        }
+        /*
+        Ask the task to stop, it finishes with Cancelled at its next loop iteration or wait.
+        Cancelling a finished task does nothing
+         */
+        pub fn cancel(&self) {
+            self.state.cancelled.store(true, Ordering::SeqCst);
+            if let Some(waker) = self.state.waker.lock().unwrap().as_ref() {
+                waker.wake();
+            }
+        }
+        pub fn is_cancelled(&self) -> bool {
+            self.state.cancelled.load(Ordering::SeqCst)
+        }
+        /*
+        Sender to the mailbox of the task, sending to a finished task fails with ChannelClosed
+         */
+        pub fn mailbox_sender(&self) -> &ChannelSender {
+            &self.state.mailbox.0
+        }
+        /*
+        Receiver of the mailbox, used by the task itself
+         */
+        pub fn mailbox(&self) -> &ChannelReceiver {
+            &self.state.mailbox.1
+        }
+    }
+    impl PartialEq for TaskHandle {
+        fn eq(&self, other: &TaskHandle) -> bool {
+            Arc::ptr_eq(&self.state, &other.state)
+        }
+    }
+    impl Eq for TaskHandle {}
+    impl fmt::Debug for TaskHandle {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "TaskHandle({})", self.state.id)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::interpreter::ByteCodeDataTypes;
+    use crate::task::task::TaskHandle;
+    use crate::waker::waker::block_on;
+    use std::thread;
+    use std::time::Duration;
+    #[test]
+    fn joining_waits_until_task_is_completed() {
+        let (task, other) = (TaskHandle::new(), TaskHandle::new());
+        assert_ne!(task, other);
+        assert!(task.poll_join(None).is_none());
+        let completing = task.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            completing.complete(Ok(ByteCodeDataTypes::Integer64(7)));
+        });
+        assert_eq!(block_on(|waker| task.poll_join(Some(waker))).unwrap(), ByteCodeDataTypes::Integer64(7));
+        assert_eq!(task.poll_join(None).unwrap().unwrap(), ByteCodeDataTypes::Integer64(7));
+        assert!(other.poll_join(None).is_none());
+    }
+}
@@ -0,0 +1,259 @@
+pub mod u256 {
+    use std::cmp::Ordering;
+    use std::fmt;
+    use std::str::FromStr;
+    /*
+    256-bit unsigned integer stored as four 64-bit limbs, least significant limb first
+     */
+    #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct U256([u64; 4]);
+    /*
+    Error returned when a U256 literal can not be parsed
+     */
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct ParseU256Error;
+
+    impl U256 {
+        pub const ZERO: U256 = U256([0; 4]);
+
+        pub fn from_u64(v: u64) -> U256 {
+            U256([v, 0, 0, 0])
+        }
+        /*
+        Converts to i64, None if the value does not fit
+         */
+        pub fn to_i64(self) -> Option<i64> {
+            if self.0[1] == 0 && self.0[2] == 0 && self.0[3] == 0 && self.0[0] <= i64::MAX as u64 {
+                Some(self.0[0] as i64)
+            } else {
+                None
+            }
+        }
+        pub fn is_zero(&self) -> bool {
+            self.0 == [0; 4]
+        }
+        pub fn checked_add(self, other: U256) -> Option<U256> {
+            let mut res = [0u64; 4];
+            let mut carry = false;
+            for (i, limb) in res.iter_mut().enumerate() {
+                let (s1, c1) = self.0[i].overflowing_add(other.0[i]);
+                let (s2, c2) = s1.overflowing_add(carry as u64);
+                *limb = s2;
+                carry = c1 || c2;
+            }
+            if carry { None } else { Some(U256(res)) }
+        }
+        pub fn checked_sub(self, other: U256) -> Option<U256> {
+            let mut res = [0u64; 4];
+            let mut borrow = false;
+            for (i, limb) in res.iter_mut().enumerate() {
+                let (d1, b1) = self.0[i].overflowing_sub(other.0[i]);
+                let (d2, b2) = d1.overflowing_sub(borrow as u64);
+                *limb = d2;
+                borrow = b1 || b2;
+            }
+            if borrow { None } else { Some(U256(res)) }
+        }
+        pub fn checked_mul(self, other: U256) -> Option<U256> {
+            let mut res = [0u64; 8];
+            for i in 0..4 {
+                let mut carry: u128 = 0;
+                for j in 0..4 {
+                    let cur = res[i + j] as u128 + (self.0[i] as u128) * (other.0[j] as u128) + carry;
+                    res[i + j] = cur as u64;
+                    carry = cur >> 64;
+                }
+                res[i + 4] = carry as u64;
+            }
+            if res[4..].iter().any(|&limb| limb != 0) {
+                None
+            } else {
+                Some(U256([res[0], res[1], res[2], res[3]]))
+            }
+        }
+        /*
+        Long division returning quotient and remainder, None on division by zero
+         */
+        pub fn checked_div_rem(self, other: U256) -> Option<(U256, U256)> {
+            if other.is_zero() {
+                return None;
+            }
+            let mut quotient = U256::ZERO;
+            let mut remainder = U256::ZERO;
+            for bit in (0..256).rev() {
+                remainder = remainder.shl(1);
+                remainder.0[0] |= self.bit(bit) as u64;
+                if remainder >= other {
+                    remainder = remainder.checked_sub(other).unwrap();
+                    quotient.0[bit / 64] |= 1 << (bit % 64);
+                }
+            }
+            Some((quotient, remainder))
+        }
+        pub fn checked_div(self, other: U256) -> Option<U256> {
+            self.checked_div_rem(other).map(|(q, _)| q)
+        }
+        pub fn checked_rem(self, other: U256) -> Option<U256> {
+            self.checked_div_rem(other).map(|(_, r)| r)
+        }
+        pub fn bitand(self, other: U256) -> U256 {
+            U256([self.0[0] & other.0[0], self.0[1] & other.0[1], self.0[2] & other.0[2], self.0[3] & other.0[3]])
+        }
+        pub fn bitor(self, other: U256) -> U256 {
+            U256([self.0[0] | other.0[0], self.0[1] | other.0[1], self.0[2] | other.0[2], self.0[3] | other.0[3]])
+        }
+        pub fn bitxor(self, other: U256) -> U256 {
+            U256([self.0[0] ^ other.0[0], self.0[1] ^ other.0[1], self.0[2] ^ other.0[2], self.0[3] ^ other.0[3]])
+        }
+        pub fn not(self) -> U256 {
+            U256([!self.0[0], !self.0[1], !self.0[2], !self.0[3]])
+        }
+        /*
+        Shift left by given number of bits, bits shifted past 256 are dropped
+         */
+        pub fn shl(self, shift: u32) -> U256 {
+            if shift >= 256 {
+                return U256::ZERO;
+            }
+            let limbs = (shift / 64) as usize;
+            let bits = shift % 64;
+            let mut res = [0u64; 4];
+            for i in (limbs..4).rev() {
+                res[i] = self.0[i - limbs] << bits;
+                if bits > 0 && i > limbs {
+                    res[i] |= self.0[i - limbs - 1] >> (64 - bits);
+                }
+            }
+            U256(res)
+        }
+        pub fn shr(self, shift: u32) -> U256 {
+            if shift >= 256 {
+                return U256::ZERO;
+            }
+            let limbs = (shift / 64) as usize;
+            let bits = shift % 64;
+            let mut res = [0u64; 4];
+            for (i, limb) in res.iter_mut().enumerate().take(4 - limbs) {
+                *limb = self.0[i + limbs] >> bits;
+                if bits > 0 && i + limbs + 1 < 4 {
+                    *limb |= self.0[i + limbs + 1] << (64 - bits);
+                }
+            }
+            U256(res)
+        }
+        fn bit(&self, index: usize) -> bool {
+            (self.0[index / 64] >> (index % 64)) & 1 == 1
+        }
+        /*
+        Divide by a small divisor, used for decimal formatting and parsing
+         */
+        fn div_rem_u64(self, divisor: u64) -> (U256, u64) {
+            let mut res = [0u64; 4];
+            let mut rem: u128 = 0;
+            for i in (0..4).rev() {
+                let cur = (rem << 64) | self.0[i] as u128;
+                res[i] = (cur / divisor as u128) as u64;
+                rem = cur % divisor as u128;
+            }
+            (U256(res), rem as u64)
+        }
+        fn from_str_radix(src: &str, radix: u32) -> Result<U256, ParseU256Error> {
+            if src.is_empty() {
+                return Err(ParseU256Error);
+            }
+            let mut value = U256::ZERO;
+            for c in src.chars() {
+                if c == '_' {
+                    continue;
+                }
+                let digit = c.to_digit(radix).ok_or(ParseU256Error)?;
+                value = value.checked_mul(U256::from_u64(radix as u64))
+                    .and_then(|v| v.checked_add(U256::from_u64(digit as u64)))
+                    .ok_or(ParseU256Error)?;
+            }
+            Ok(value)
+        }
+    }
+    impl From<u64> for U256 {
+        fn from(v: u64) -> U256 {
+            U256::from_u64(v)
+        }
+    }
+    impl Ord for U256 {
+        fn cmp(&self, other: &U256) -> Ordering {
+            for i in (0..4).rev() {
+                match self.0[i].cmp(&other.0[i]) {
+                    Ordering::Equal => continue,
+                    ord => return ord,
+                }
+            }
+            Ordering::Equal
+        }
+    }
+    impl PartialOrd for U256 {
+        fn partial_cmp(&self, other: &U256) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    /*
+    Parses decimal literals, or hex literals prefixed with 0x
+     */
+    impl FromStr for U256 {
+        type Err = ParseU256Error;
+        fn from_str(s: &str) -> Result<U256, ParseU256Error> {
+            match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                Some(hex) => U256::from_str_radix(hex, 16),
+                None => U256::from_str_radix(s, 10),
+            }
+        }
+    }
+    impl fmt::Display for U256 {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            if self.is_zero() {
+                return write!(f, "0");
+            }
+            let mut digits = vec![];
+            let mut value = *self;
+            while !value.is_zero() {
+                let (q, r) = value.div_rem_u64(10);
+                digits.push(char::from(b'0' + r as u8));
+                value = q;
+            }
+            write!(f, "{}", digits.iter().rev().collect::<String>())
+        }
+    }
+    impl fmt::Debug for U256 {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::u256::u256::U256;
+    #[test]
+    fn parse_and_format_decimal_and_hex() {
+        let max = "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+        assert_eq!(max.parse::<U256>().unwrap(), U256::ZERO.not());
+        assert_eq!(format!("{}", U256::ZERO.not()), max);
+        assert_eq!("0xff".parse::<U256>().unwrap(), U256::from(255));
+        assert!("0x1g".parse::<U256>().is_err());
+        assert!("115792089237316195423570985008687907853269984665640564039457584007913129639936".parse::<U256>().is_err());
+    }
+    #[test]
+    fn arithmetic_and_bitwise() {
+        let a: U256 = "340282366920938463463374607431768211456".parse().unwrap(); // 2^128
+        assert_eq!(U256::from(1).shl(128), a);
+        assert_eq!(a.shr(127), U256::from(2));
+        assert_eq!(a.checked_mul(a), None);
+        assert_eq!(U256::ZERO.not().checked_add(U256::from(1)), None);
+        assert_eq!(U256::ZERO.checked_sub(U256::from(1)), None);
+        assert_eq!(a.checked_mul(U256::from(3)).unwrap().checked_div_rem(a), Some((U256::from(3), U256::ZERO)));
+        assert_eq!(U256::from(7).checked_rem(U256::from(4)), Some(U256::from(3)));
+        assert_eq!(U256::from(1).checked_div(U256::ZERO), None);
+        assert_eq!(U256::from(0b1100).bitand(U256::from(0b1010)), U256::from(0b1000));
+        let max: U256 = "115792089237316195423570985008687907853269984665640564039457584007913129639935".parse().unwrap();
+        assert_eq!(U256::ZERO.not(), max);
+        assert_eq!(max.checked_add(U256::from(1)), None);
+    }
+}
@@ -0,0 +1,112 @@
+pub mod wait_group {
+    use crate::interpreter::interpreter::ByteCodeError;
+    use crate::waker::waker::{WaitQueue, Waker};
+    use std::fmt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    /*
+    Counter to give every wait group a unique id
+     */
+    static NEXT_WAIT_GROUP_ID: AtomicU64 = AtomicU64::new(1);
+    /*
+    Number of workers that did not call done yet and the tasks waiting for it to drop to zero
+     */
+    struct WaitGroupState {
+        count: i64,
+        waiters: WaitQueue,
+    }
+    struct WaitGroupCell {
+        id: u64,
+        state: Mutex<WaitGroupState>,
+    }
+    /*
+    Wait group shared by every task holding a copy of it, created by WAITGROUP
+     */
+    #[derive(Clone)]
+    pub struct WaitGroup {
+        cell: Arc<WaitGroupCell>,
+    }
+
+    impl WaitGroup {
+        pub fn new() -> WaitGroup {
+            WaitGroup {
+                cell: Arc::new(WaitGroupCell {
+                    id: NEXT_WAIT_GROUP_ID.fetch_add(1, Ordering::Relaxed),
+                    state: Mutex::new(WaitGroupState { count: 0, waiters: WaitQueue::default() }),
+                }),
+            }
+        }
+        /*
+        Add delta to the counter, waiting tasks are woken when it drops to zero.
+        The counter is left unchanged and NegativeWaitGroup returned when it would drop below zero
+         */
+        pub fn add(&self, delta: i64) -> Result<(), ByteCodeError> {
+            let mut state = self.cell.state.lock().unwrap();
+            match state.count.checked_add(delta) {
+                Some(count) if count >= 0 => {
+                    state.count = count;
+                    if count == 0 {
+                        state.waiters.wake_all();
+                    }
+                    Ok(())
+                },
+                Some(_) => Err(ByteCodeError::NegativeWaitGroup),
+                None => Err(ByteCodeError::Overflow),
+            }
+        }
+        pub fn done(&self) -> Result<(), ByteCodeError> {
+            self.add(-1)
+        }
+        /*
+        Some when the counter is zero, otherwise register waker (when given) and return None
+         */
+        pub fn poll_wait(&self, waker: Option<&Waker>) -> Option<()> {
+            let mut state = self.cell.state.lock().unwrap();
+            if state.count == 0 {
+                return Some(());
+            }
+            if let Some(waker) = waker {
+                state.waiters.register(waker);
+            }
+            None
+        }
+    }
+    impl Default for WaitGroup {
+        fn default() -> WaitGroup {
+            WaitGroup::new()
+        }
+    }
+    impl PartialEq for WaitGroup {
+        fn eq(&self, other: &WaitGroup) -> bool {
+            Arc::ptr_eq(&self.cell, &other.cell)
+        }
+    }
+    impl Eq for WaitGroup {}
+    impl fmt::Debug for WaitGroup {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "WaitGroup({})", self.cell.id)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::interpreter::ByteCodeError;
+    use crate::wait_group::wait_group::WaitGroup;
+    use crate::waker::waker::block_on;
+    use std::thread;
+    #[test]
+    fn wait_returns_when_every_worker_is_done() {
+        let wg = WaitGroup::new();
+        wg.add(4).unwrap();
+        let workers: Vec<_> = (0..4).map(|_| {
+            let wg = wg.clone();
+            thread::spawn(move || wg.done().unwrap())
+        }).collect();
+        block_on(|waker| wg.poll_wait(Some(waker)));
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert!(matches!(wg.done(), Err(ByteCodeError::NegativeWaitGroup)));
+        assert!(wg.poll_wait(None).is_some());
+    }
+}
@@ -0,0 +1,199 @@
+pub mod waker {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::time::Instant;
+    /*
+    What waking does - unpark a thread waiting on its signal, or call back into the scheduler of a green task
+     */
+    enum WakeTarget {
+        Thread { notified: Mutex<bool>, condvar: Condvar },
+        Callback(Box<dyn Fn() + Send + Sync>),
+    }
+    /*
+    Wake target with a generation that changes every time a new wait starts
+     */
+    struct WakerInner {
+        target: WakeTarget,
+        generation: AtomicU64,
+    }
+    /*
+    Handle used to wake a task waiting on channels or other synchronisation values
+     */
+    #[derive(Clone)]
+    pub struct Waker {
+        inner: Arc<WakerInner>,
+    }
+    /*
+    Wakers registered on a synchronisation value, registrations of finished waits are ignored
+     */
+    #[derive(Default)]
+    pub struct WaitQueue {
+        waiters: Vec<(Waker, u64)>,
+    }
+    thread_local! {
+        static CURRENT: Waker = Waker::new(WakeTarget::Thread { notified: Mutex::new(false), condvar: Condvar::new() });
+    }
+
+    impl Waker {
+        fn new(target: WakeTarget) -> Waker {
+            Waker { inner: Arc::new(WakerInner { target, generation: AtomicU64::new(0) }) }
+        }
+        /*
+        Waker of the current thread
+         */
+        pub fn current() -> Waker {
+            CURRENT.with(|waker| waker.clone())
+        }
+        /*
+        Waker that calls wake instead of unparking a thread, used for green tasks
+         */
+        pub fn from_fn(wake: impl Fn() + Send + Sync + 'static) -> Waker {
+            Waker::new(WakeTarget::Callback(Box::new(wake)))
+        }
+        pub fn wake(&self) {
+            match &self.inner.target {
+                WakeTarget::Thread { notified, condvar } => {
+                    *notified.lock().unwrap() = true;
+                    condvar.notify_all();
+                },
+                WakeTarget::Callback(wake) => wake(),
+            }
+        }
+        fn generation(&self) -> u64 {
+            self.inner.generation.load(Ordering::SeqCst)
+        }
+        /*
+        Start a new wait, every registration made before becomes stale
+         */
+        pub fn renew(&self) {
+            self.inner.generation.fetch_add(1, Ordering::SeqCst);
+        }
+        /*
+        Park the current thread until its waker is woken or deadline passed
+         */
+        pub fn wait(&self, deadline: Option<Instant>) {
+            self.park(deadline);
+            self.clear();
+        }
+        /*
+        Park the current thread until its waker is woken, the waker stays notified until clear is called
+         */
+        pub fn wait_notified(&self) {
+            self.park(None);
+        }
+        fn park(&self, deadline: Option<Instant>) {
+            if let WakeTarget::Thread { notified: flag, condvar } = &self.inner.target {
+                let mut notified = flag.lock().unwrap();
+                while !*notified {
+                    match deadline {
+                        Some(deadline) => {
+                            let now = Instant::now();
+                            if now >= deadline {
+                                break;
+                            }
+                            notified = condvar.wait_timeout(notified, deadline - now).unwrap().0;
+                        },
+                        None => notified = condvar.wait(notified).unwrap(),
+                    }
+                }
+            }
+        }
+        /*
+        True when a thread waker was woken and its thread did not continue yet
+         */
+        pub fn is_notified(&self) -> bool {
+            match &self.inner.target {
+                WakeTarget::Thread { notified, .. } => *notified.lock().unwrap(),
+                WakeTarget::Callback(_) => false,
+            }
+        }
+        pub fn clear(&self) {
+            if let WakeTarget::Thread { notified, .. } = &self.inner.target {
+                *notified.lock().unwrap() = false;
+            }
+        }
+    }
+
+    impl WaitQueue {
+        pub fn register(&mut self, waker: &Waker) {
+            let generation = waker.generation();
+            self.waiters.retain(|(w, g)| w.generation() == *g && !Arc::ptr_eq(&w.inner, &waker.inner));
+            self.waiters.push((waker.clone(), generation));
+        }
+        /*
+        Number of tasks still waiting
+         */
+        pub fn len(&self) -> usize {
+            self.waiters.iter().filter(|(w, g)| w.generation() == *g).count()
+        }
+        pub fn wake_all(&mut self) {
+            for (waker, generation) in self.waiters.drain(..) {
+                if waker.generation() == generation {
+                    waker.wake();
+                }
+            }
+        }
+    }
+    /*
+    Block current thread until poll returns a value. Poll must register the waker on everything
+    it waits for before returning None, so a change between poll and wait is not missed
+     */
+    pub fn block_on<T>(poll: impl FnMut(&Waker) -> Option<T>) -> T {
+        block_until(None, poll).unwrap()
+    }
+    /*
+    Same as block_on but gives up at deadline and returns None
+     */
+    pub fn block_until<T>(deadline: Option<Instant>, mut poll: impl FnMut(&Waker) -> Option<T>) -> Option<T> {
+        let waker = Waker::current();
+        loop {
+            waker.renew();
+            if let Some(v) = poll(&waker) {
+                waker.renew();
+                return Some(v);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                waker.renew();
+                return None;
+            }
+            waker.wait(deadline);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::waker::waker::{block_on, block_until, WaitQueue};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    #[test]
+    fn blocked_thread_is_woken_by_wait_queue() {
+        let shared = Arc::new(Mutex::new((None, WaitQueue::default())));
+        let producer = shared.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            let mut state = producer.lock().unwrap();
+            state.0 = Some(5);
+            state.1.wake_all();
+        });
+        let value = block_on(|waker| {
+            let mut state = shared.lock().unwrap();
+            if state.0.is_none() {
+                state.1.register(waker);
+            }
+            state.0
+        });
+        assert_eq!(value, 5);
+        assert_eq!(shared.lock().unwrap().1.len(), 0);
+    }
+    #[test]
+    fn wait_gives_up_at_deadline_and_registration_becomes_stale() {
+        let mut queue = WaitQueue::default();
+        let result: Option<()> = block_until(Some(Instant::now() + Duration::from_millis(10)), |waker| {
+            queue.register(waker);
+            None
+        });
+        assert!(result.is_none());
+        assert_eq!(queue.len(), 0);
+    }
+}